@@ -0,0 +1,325 @@
+use crate::{
+    config::{Archive, SizeBudget},
+    git::tag::Tag,
+    github::asset::Asset,
+    naming::NameContext,
+};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const MANIFEST_FILE_NAME: &str = "artifacts.json";
+
+/// An archive produced by the release, with the size of what it packs
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub name: String,
+    pub target: Option<String>,
+    pub path: PathBuf,
+    pub checksum: Option<String>,
+    pub binary_size: u64,
+    pub archive_size: u64,
+    pub previous_archive_size: Option<u64>,
+    /// The values the asset name was rendered from
+    #[serde(skip)]
+    context: Option<NameContext>,
+}
+
+impl Artifact {
+    pub fn new(
+        asset: &Asset,
+        target: Option<String>,
+        context: &NameContext,
+        binaries: impl IntoIterator<Item = PathBuf>,
+    ) -> Result<Self> {
+        let mut binary_size = 0;
+        for binary in binaries {
+            binary_size += file_size(&binary)?;
+        }
+
+        Ok(Artifact {
+            name: asset.name.to_owned(),
+            target,
+            path: asset.path.to_owned(),
+            checksum: asset.checksum.to_owned(),
+            binary_size,
+            archive_size: file_size(&asset.path)?,
+            previous_archive_size: None,
+            context: Some(context.to_owned()),
+        })
+    }
+
+    /// The name the asset had in the previous release, rendered from the same template with
+    /// the previous tag, or with the tag in its name replaced
+    fn previous_name(&self, tag: &str, previous: &Tag, archive: &Archive) -> String {
+        let rendered = self.context.as_ref().and_then(|context| {
+            let stem = archive.asset_name(context).ok()?;
+            let extension = self.name.strip_prefix(&stem)?;
+            let previous_stem = archive.asset_name(&context.with_tag(previous)).ok()?;
+            Some(format!("{}{}", previous_stem, extension))
+        });
+
+        rendered.unwrap_or_else(|| self.name.replace(tag, previous.name()))
+    }
+
+    /// Growth of the archive since the previous release, in percent
+    pub fn growth_percent(&self) -> Option<f64> {
+        match self.previous_archive_size {
+            Some(previous) if previous > 0 => {
+                Some((self.archive_size as f64 - previous as f64) * 100.0 / previous as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The asset sizes of the release preceding the current one
+#[derive(Debug, Clone)]
+pub struct PreviousRelease {
+    pub tag: Tag,
+    pub asset_sizes: HashMap<String, u64>,
+}
+
+/// The manifest of every artifact of a release
+#[derive(Debug, Serialize)]
+pub struct Artifacts {
+    tag: String,
+    previous_tag: Option<String>,
+    artifacts: Vec<Artifact>,
+}
+
+impl Artifacts {
+    pub fn new(tag: &Tag) -> Self {
+        Artifacts {
+            tag: tag.name().to_owned(),
+            previous_tag: None,
+            artifacts: vec![],
+        }
+    }
+
+    pub fn push(&mut self, artifact: Artifact) {
+        self.artifacts.push(artifact);
+    }
+
+    /// Matches the artifacts with the previous release assets, named from the same template
+    pub fn compare(&mut self, previous: &PreviousRelease, archive: &Archive) {
+        self.previous_tag = Some(previous.tag.name().to_owned());
+
+        for artifact in &mut self.artifacts {
+            let name = artifact.previous_name(&self.tag, &previous.tag, archive);
+            artifact.previous_archive_size = previous.asset_sizes.get(&name).copied();
+            if artifact.previous_archive_size.is_none() {
+                log::warn!(
+                    "{} has no matching asset in release {}, expected {}, its growth is not checked",
+                    artifact.name,
+                    previous.tag.name(),
+                    name
+                );
+            }
+        }
+    }
+
+    pub fn print_sizes(&self) {
+        log::info!(
+            "{:<48} {:>12} {:>12} {:>12}",
+            "artifact",
+            "binary",
+            "archive",
+            "growth"
+        );
+        for artifact in &self.artifacts {
+            let growth = artifact
+                .growth_percent()
+                .map(|growth| format!("{:+.1}%", growth))
+                .unwrap_or_else(|| "-".to_owned());
+
+            log::info!(
+                "{:<48} {:>12} {:>12} {:>12}",
+                artifact.name,
+                artifact.binary_size,
+                artifact.archive_size,
+                growth
+            );
+        }
+    }
+
+    /// Writes the manifest as json in the given directory
+    pub fn write(&self, dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let path = dir.join(MANIFEST_FILE_NAME);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+
+        Ok(path)
+    }
+
+    /// Fails when any artifact exceeds the budget, listing every violation
+    pub fn check_budget(&self, budget: &SizeBudget) -> Result<()> {
+        let mut violations = vec![];
+
+        for artifact in &self.artifacts {
+            if let Some(max) = budget.max_binary_size {
+                if artifact.binary_size > max {
+                    violations.push(format!(
+                        "{}: binary size {} exceeds {} bytes",
+                        artifact.name, artifact.binary_size, max
+                    ));
+                }
+            }
+
+            if let Some(max) = budget.max_archive_size {
+                if artifact.archive_size > max {
+                    violations.push(format!(
+                        "{}: archive size {} exceeds {} bytes",
+                        artifact.name, artifact.archive_size, max
+                    ));
+                }
+            }
+
+            if let (Some(max), Some(growth)) =
+                (budget.max_growth_percent, artifact.growth_percent())
+            {
+                if growth > max {
+                    violations.push(format!(
+                        "{}: archive grew by {:.1}%, more than {}%",
+                        artifact.name, growth, max
+                    ));
+                }
+            }
+        }
+
+        if !violations.is_empty() {
+            bail!(anyhow::anyhow!(
+                "size budget exceeded:\n{}",
+                violations.join("\n")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn file_size(path: impl AsRef<Path>) -> Result<u64> {
+    let path = path.as_ref();
+    let metadata = fs::metadata(path).with_context(|| format!("Cannot read {}", path.display()))?;
+
+    Ok(metadata.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        build::{arch::Arch, os::Os},
+        cargo::arch_os_matrix::ArchOsMatrixEntry,
+        compression::Compression,
+    };
+
+    fn artifact(name: &str, binary_size: u64, archive_size: u64) -> Artifact {
+        Artifact {
+            name: name.to_owned(),
+            target: None,
+            path: PathBuf::from(name),
+            checksum: None,
+            binary_size,
+            archive_size,
+            previous_archive_size: None,
+            context: None,
+        }
+    }
+
+    fn artifacts() -> Artifacts {
+        let mut artifacts = Artifacts::new(&Tag::new("v1.1.0"));
+        artifacts.push(artifact("tool-v1.1.0-x86_64-linux.tar.gz", 4000, 1200));
+        artifacts.push(artifact("tool-v1.1.0-aarch64-apple.tar.gz", 3000, 1000));
+        artifacts
+    }
+
+    #[test]
+    fn should_match_the_previous_assets_by_name() {
+        let mut artifacts = artifacts();
+        artifacts.compare(
+            &PreviousRelease {
+                tag: Tag::new("v1.0.0"),
+                asset_sizes: HashMap::from([
+                    ("tool-v1.0.0-x86_64-linux.tar.gz".to_owned(), 1000),
+                    ("tool-v1.0.0-x86_64-linux.tar.gz.sha256".to_owned(), 90),
+                ]),
+            },
+            &Archive::default(),
+        );
+
+        let sizes: Vec<_> = artifacts
+            .artifacts
+            .iter()
+            .map(|artifact| artifact.previous_archive_size)
+            .collect();
+        assert_eq!(sizes, vec![Some(1000), None]);
+        assert_eq!(artifacts.artifacts[0].growth_percent(), Some(20.0));
+    }
+
+    #[test]
+    fn should_match_the_previous_assets_named_without_the_tag() {
+        let archive: Archive =
+            serde_yaml::from_str("name_template: '{{ name }}_{{ version }}_{{ target }}'").unwrap();
+        let target = ArchOsMatrixEntry::new(&Arch::Amd64, &Os::UnknownLinuxGnu);
+        let tag = Tag::new("v1.1.0");
+        let context = NameContext::for_target("tool", &tag, &target, &Compression::TarGz);
+
+        let mut artifacts = Artifacts::new(&tag);
+        artifacts.push(Artifact {
+            context: Some(context),
+            ..artifact("tool_1.1.0_x86_64-unknown-linux-gnu.tar.gz", 4000, 1200)
+        });
+        artifacts.compare(
+            &PreviousRelease {
+                tag: Tag::new("v1.0.0"),
+                asset_sizes: HashMap::from([(
+                    "tool_1.0.0_x86_64-unknown-linux-gnu.tar.gz".to_owned(),
+                    1000,
+                )]),
+            },
+            &archive,
+        );
+
+        assert_eq!(artifacts.artifacts[0].previous_archive_size, Some(1000));
+    }
+
+    #[test]
+    fn should_fail_when_the_budget_is_exceeded() {
+        let mut artifacts = artifacts();
+        artifacts.compare(
+            &PreviousRelease {
+                tag: Tag::new("v1.0.0"),
+                asset_sizes: HashMap::from([("tool-v1.0.0-x86_64-linux.tar.gz".to_owned(), 1000)]),
+            },
+            &Archive::default(),
+        );
+
+        let within = SizeBudget {
+            max_binary_size: Some(4000),
+            max_archive_size: Some(1200),
+            max_growth_percent: Some(20.0),
+        };
+        assert!(artifacts.check_budget(&within).is_ok());
+
+        let growth = SizeBudget {
+            max_growth_percent: Some(10.0),
+            ..Default::default()
+        };
+        let error = artifacts.check_budget(&growth).unwrap_err().to_string();
+        assert!(error.contains("tool-v1.1.0-x86_64-linux.tar.gz: archive grew by 20.0%"));
+
+        let binary = SizeBudget {
+            max_binary_size: Some(3500),
+            ..Default::default()
+        };
+        assert!(artifacts.check_budget(&binary).is_err());
+    }
+}
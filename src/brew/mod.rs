@@ -15,8 +15,10 @@ use crate::{
     github::{github_client, handler::BuilderExecutor},
 };
 use crate::{
-    build::arch::Arch,
-    config::{BrewConfig, CommitterConfig, PullRequestConfig},
+    build::{arch::Arch, binary::Binary, os::Os},
+    checksum::ChecksumAlgorithm,
+    compression::Compression,
+    config::{Archive, BrewConfig, CommitterConfig, PullRequestConfig},
     git,
 };
 use anyhow::{Context, Result};
@@ -25,6 +27,9 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use template::Template;
 
+/// The hash of the formula checksums, the only one Homebrew accepts whatever the release uploads
+const FORMULA_CHECKSUM_ALGORITHM: ChecksumAlgorithm = ChecksumAlgorithm::Sha256;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Brew {
     pub name: String,
@@ -36,25 +41,33 @@ pub struct Brew {
     pub caveats: String,
     pub commit_message: String,
     pub commit_author: Option<CommitterConfig>,
-    pub install_info: String,
     pub repository: Repository,
     #[serde(flatten)]
     #[serde(rename(serialize = "version"))]
     pub tag: Tag,
     pub pull_request: Option<PullRequestConfig>,
     pub targets: Targets,
+    pub algorithm: ChecksumAlgorithm,
     pub template: Template,
 }
 
 impl Brew {
-    pub fn new(brew: BrewConfig, version: Tag, packages: Vec<Package>) -> Brew {
-        let targets = Targets::from(packages);
+    pub fn new(
+        brew: BrewConfig,
+        version: Tag,
+        packages: Vec<Package>,
+        binaries: &[Binary],
+        archive: &Archive,
+    ) -> Brew {
+        let targets = Targets::new(packages, |package| match &brew.install {
+            Some(install) => install.to_owned(),
+            None => default_install(binaries, package, archive),
+        });
         let template = Template::from(&targets);
         Brew {
             name: captalize(brew.name),
             description: brew.description,
             homepage: brew.homepage,
-            install_info: brew.install,
             repository: brew.repository,
             tag: version,
             targets,
@@ -65,6 +78,7 @@ impl Brew {
             commit_message: brew.commit_message,
             commit_author: brew.commit_author,
             pull_request: brew.pull_request,
+            algorithm: FORMULA_CHECKSUM_ALGORITHM,
             template,
         }
     }
@@ -75,22 +89,40 @@ pub struct BrewArch {
     pub arch: Arch,
     pub url: String,
     pub hash: String,
+    pub install: String,
 }
 
 impl BrewArch {
-    pub fn new(arch: Arch, url: impl Into<String>, hash: impl Into<String>) -> Self {
+    pub fn new(
+        arch: Arch,
+        url: impl Into<String>,
+        hash: impl Into<String>,
+        install: impl Into<String>,
+    ) -> Self {
         Self {
             arch,
             url: url.into(),
             hash: hash.into(),
+            install: install.into(),
         }
     }
 }
 
-pub async fn publish(brew_config: BrewConfig, packages: Vec<Package>) -> Result<String> {
+pub async fn publish(
+    brew_config: BrewConfig,
+    packages: Vec<Package>,
+    binaries: &[Binary],
+    archive: &Archive,
+) -> Result<String> {
     log::debug!("packages: {:?}", packages);
 
-    let brew = Brew::new(brew_config, git::get_current_tag(cwd!())?, packages);
+    let brew = Brew::new(
+        brew_config,
+        git::get_current_tag(cwd!())?,
+        packages,
+        binaries,
+        archive,
+    );
     log::debug!("Rendering Formula template {}", brew.template.to_string());
 
     let data = serialize(&brew)?;
@@ -128,6 +160,25 @@ fn write_file(file_name: impl Into<String>, data: impl Into<String>) -> Result<(
     Ok(())
 }
 
+/// Installs every binary of the archive, or the downloaded bare binary under its own name
+fn default_install(binaries: &[Binary], package: &Package, archive: &Archive) -> String {
+    let os = package.os.to_owned().unwrap_or_else(Os::host);
+    if let (Compression::Binary, [binary]) = (archive.compression(&os), binaries) {
+        return format!(
+            "bin.install \"{}\" => \"{}\"",
+            package.name,
+            binary.archive_name()
+        );
+    }
+
+    let binaries = binaries
+        .iter()
+        .map(|binary| format!("\"{}\"", binary.archive_name()))
+        .join(", ");
+
+    format!("bin.install {}", binaries)
+}
+
 fn captalize(mut string: String) -> String {
     format!("{}{string}", string.remove(0).to_uppercase())
 }
@@ -183,27 +234,62 @@ async fn push_formula(brew: Brew) -> Result<()> {
     Ok(())
 }
 
-impl From<Vec<Package>> for Targets {
-    fn from(value: Vec<Package>) -> Targets {
+impl Targets {
+    /// Groups the packages by os, each with the install block of its asset
+    fn new(value: Vec<Package>, install: impl Fn(&Package) -> String) -> Targets {
         let targets: Vec<Target> = if value.is_empty() {
             vec![]
         } else if value.len() == 1 {
             let target = vec![Target::Single(SingleTarget::new(
                 &value[0].url,
                 &value[0].sha256,
+                install(&value[0]),
             ))];
             target
         } else {
-            let group = value
-                .iter()
-                .cloned()
-                .group_by(|p| p.os.to_owned())
+            let (musl, value): (Vec<Package>, Vec<Package>) = value
+                .into_iter()
+                .partition(|package| package.os == Some(Os::UnknownLinuxMusl));
+
+            let mut groups: Vec<(Os, Vec<Package>)> = vec![];
+            for package in value {
+                let os = package.os.to_owned().unwrap();
+                match groups.iter_mut().find(|(group_os, _)| *group_os == os) {
+                    Some((_, packages)) => packages.push(package),
+                    None => groups.push((os, vec![package])),
+                }
+            }
+
+            // brew has a single linux block, the musl binaries fill in the CPU types
+            // without a gnu one
+            for package in musl {
+                match groups
+                    .iter_mut()
+                    .find(|(os, _)| *os == Os::UnknownLinuxGnu || *os == Os::UnknownLinuxMusl)
+                {
+                    Some((_, packages)) if packages.iter().any(|p| p.arch == package.arch) => {
+                        log::debug!("preferring the gnu binary over {}", package.name)
+                    }
+                    Some((_, packages)) => packages.push(package),
+                    None => groups.push((Os::UnknownLinuxMusl, vec![package])),
+                }
+            }
+
+            let group = groups
                 .into_iter()
-                .map(|g| MultiTarget {
-                    os: g.0.unwrap(),
-                    archs: g
-                        .1
-                        .map(|p| BrewArch::new(p.arch.unwrap(), p.url, p.sha256))
+                .map(|(os, packages)| MultiTarget {
+                    os,
+                    universal: packages
+                        .iter()
+                        .find(|p| p.arch.is_none())
+                        .map(|p| SingleTarget::new(&p.url, &p.sha256, install(p))),
+                    archs: packages
+                        .into_iter()
+                        .filter_map(|p| {
+                            let install = install(&p);
+                            p.arch
+                                .map(|arch| BrewArch::new(arch, p.url, p.sha256, install))
+                        })
                         .collect(),
                 })
                 .map(Target::Multi)
@@ -224,3 +310,162 @@ impl From<CommitterConfig> for Committer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChecksumConfig;
+
+    #[test]
+    fn should_install_every_binary_by_default() {
+        let binaries = vec![
+            Binary::new("tool"),
+            Binary {
+                name: "tool-daemon".to_string(),
+                rename: Some("toold".to_string()),
+            },
+        ];
+
+        let package = Package::new("tool.tar.gz", None, None, "url", "sha", false);
+
+        assert_eq!(
+            default_install(&binaries, &package, &Archive::default()),
+            r#"bin.install "tool", "toold""#
+        );
+    }
+
+    #[test]
+    fn should_install_the_bare_binary_under_its_name() -> Result<()> {
+        let archive: Archive = serde_yaml::from_str("compression: binary")?;
+        let package =
+            |os: Os, url: &str| Package::new(url, Some(os), Some(Arch::Amd64), url, "sha", false);
+
+        let brew = Brew::new(
+            serde_yaml::from_str("name: tool\nrepository:\n  owner: owner\n  name: tap")?,
+            Tag::new("v1.0.0"),
+            vec![
+                package(Os::UnknownLinuxGnu, "tool-v1.0.0-x86_64-linux"),
+                package(Os::AppleDarwin, "tool-v1.0.0-x86_64-apple"),
+            ],
+            &[Binary::new("tool")],
+            &archive,
+        );
+        let formula = serialize(&brew)?;
+
+        assert!(formula.contains(r#"bin.install "tool-v1.0.0-x86_64-linux" => "tool""#));
+        assert!(formula.contains(r#"bin.install "tool-v1.0.0-x86_64-apple" => "tool""#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_the_sha256_of_the_formula_without_checksum_algorithms() -> Result<()> {
+        let checksum: ChecksumConfig = serde_yaml::from_str("algorithms: []")?;
+        assert_eq!(checksum.algorithms(), vec![ChecksumAlgorithm::Sha256]);
+
+        let brew = Brew::new(
+            serde_yaml::from_str("name: tool\nrepository:\n  owner: owner\n  name: tap")?,
+            Tag::new("v1.0.0"),
+            vec![Package::new(
+                "tool.tar.gz",
+                None,
+                None,
+                "url",
+                "tool-sha",
+                false,
+            )],
+            &[Binary::new("tool")],
+            &Archive::default(),
+        );
+        let formula = serialize(&brew)?;
+
+        assert!(formula.contains(r#"sha256 "tool-sha""#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_use_the_universal_binary_for_every_darwin_cpu() -> Result<()> {
+        let package = |os: Os, arch: Option<Arch>, url: &str| {
+            Package::new(url, Some(os), arch, url, format!("{}-sha", url), false)
+        };
+        let packages = vec![
+            package(Os::UnknownLinuxGnu, Some(Arch::Amd64), "linux-x86_64"),
+            package(Os::AppleDarwin, Some(Arch::Amd64), "apple-x86_64"),
+            package(Os::UnknownLinuxGnu, Some(Arch::Arm64), "linux-aarch64"),
+            package(Os::AppleDarwin, Some(Arch::Arm64), "apple-aarch64"),
+            package(Os::AppleDarwin, None, "apple-universal"),
+        ];
+
+        let brew = Brew::new(
+            serde_yaml::from_str("name: tool\nrepository:\n  owner: owner\n  name: tap")?,
+            Tag::new("v1.0.0"),
+            packages,
+            &[Binary::new("tool")],
+            &Archive::default(),
+        );
+        let formula = serialize(&brew)?;
+
+        assert_eq!(brew.targets.0.len(), 2);
+        assert!(formula.contains(r#"url "linux-x86_64""#));
+        assert!(formula.contains(r#"url "linux-aarch64""#));
+        assert!(formula.contains(r#"url "apple-universal""#));
+        assert!(!formula.contains(r#"url "apple-x86_64""#));
+        assert_eq!(formula.matches(r#"bin.install "tool""#).count(), 3);
+        assert!(formula.contains(r#"sha256 "apple-universal-sha""#));
+        assert!(formula.contains(r#"sha256 "linux-aarch64-sha""#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_install_the_musl_binaries_on_linux() -> Result<()> {
+        let package = |os: Os, arch: Arch, url: &str| {
+            Package::new(
+                url,
+                Some(os),
+                Some(arch),
+                url,
+                format!("{}-sha", url),
+                false,
+            )
+        };
+        let config = "name: tool\nrepository:\n  owner: owner\n  name: tap";
+
+        let brew = Brew::new(
+            serde_yaml::from_str(config)?,
+            Tag::new("v1.0.0"),
+            vec![
+                package(Os::UnknownLinuxMusl, Arch::Amd64, "musl-x86_64"),
+                package(Os::UnknownLinuxMusl, Arch::Arm64, "musl-aarch64"),
+            ],
+            &[Binary::new("tool")],
+            &Archive::default(),
+        );
+        let formula = serialize(&brew)?;
+
+        assert!(formula.contains("on_linux do"));
+        assert!(formula.contains(r#"url "musl-x86_64""#));
+        assert!(formula.contains(r#"url "musl-aarch64""#));
+
+        let brew = Brew::new(
+            serde_yaml::from_str(config)?,
+            Tag::new("v1.0.0"),
+            vec![
+                package(Os::UnknownLinuxGnu, Arch::Amd64, "gnu-x86_64"),
+                package(Os::UnknownLinuxMusl, Arch::Amd64, "musl-x86_64"),
+                package(Os::UnknownLinuxMusl, Arch::Arm64, "musl-aarch64"),
+            ],
+            &[Binary::new("tool")],
+            &Archive::default(),
+        );
+        let formula = serialize(&brew)?;
+
+        assert_eq!(formula.matches("on_linux do").count(), 1);
+        assert!(formula.contains(r#"url "gnu-x86_64""#));
+        assert!(!formula.contains(r#"url "musl-x86_64""#));
+        assert!(formula.contains(r#"url "musl-aarch64""#));
+
+        Ok(())
+    }
+}
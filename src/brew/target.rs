@@ -15,19 +15,27 @@ impl Targets {
 pub struct MultiTarget {
     pub os: Os,
     pub archs: Vec<BrewArch>,
+    /// A single binary for every CPU type of the os, preferred over the archs
+    pub universal: Option<SingleTarget>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SingleTarget {
     pub url: String,
     pub hash: String,
+    pub install: String,
 }
 
 impl SingleTarget {
-    pub fn new(url: impl Into<String>, hash: impl Into<String>) -> Self {
+    pub fn new(
+        url: impl Into<String>,
+        hash: impl Into<String>,
+        install: impl Into<String>,
+    ) -> Self {
         Self {
             url: url.into(),
             hash: hash.into(),
+            install: install.into(),
         }
     }
 }
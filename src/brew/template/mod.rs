@@ -2,6 +2,7 @@ use crate::brew::target::{Target, Targets};
 use anyhow::Result;
 use handlebars::{handlebars_helper, Handlebars};
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
 pub fn handlebars<'hb>() -> Result<Handlebars<'hb>> {
     let mut hb = Handlebars::new();
@@ -25,11 +26,11 @@ pub enum Template {
     SingleTarget,
 }
 
-impl ToString for Template {
-    fn to_string(&self) -> String {
+impl Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Template::MultiTarget => "multi_target".to_string(),
-            Template::SingleTarget => "single_target".to_string(),
+            Template::MultiTarget => write!(f, "multi_target"),
+            Template::SingleTarget => write!(f, "single_target"),
         }
     }
 }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Arch {
     #[serde(rename(deserialize = "x86_64"))]
     #[serde(rename(deserialize = "amd64"))]
@@ -11,6 +12,21 @@ pub enum Arch {
     Arm64,
 }
 
+impl Arch {
+    /// The arch this binary was built for, if it is a supported one
+    pub fn host() -> Option<Arch> {
+        if cfg!(target_arch = "x86_64") {
+            Some(Arch::Amd64)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Arch::Arm64)
+        } else if cfg!(target_arch = "arm") {
+            Some(Arch::Arm)
+        } else {
+            None
+        }
+    }
+}
+
 impl From<String> for Arch {
     fn from(value: String) -> Self {
         match value.to_lowercase().as_str() {
@@ -22,12 +38,12 @@ impl From<String> for Arch {
     }
 }
 
-impl ToString for Arch {
-    fn to_string(&self) -> String {
+impl Display for Arch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Arch::Amd64 => "x86_64".to_string(),
-            Arch::Arm => "arm".to_string(),
-            Arch::Arm64 => "aarch64".to_string(),
+            Arch::Amd64 => write!(f, "x86_64"),
+            Arch::Arm => write!(f, "arm"),
+            Arch::Arm64 => write!(f, "aarch64"),
         }
     }
 }
@@ -0,0 +1,123 @@
+use super::os::Os;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A `[[bin]]` target to be released, optionally renamed inside the archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "BinaryConfig")]
+pub struct Binary {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename: Option<String>,
+}
+
+impl Binary {
+    pub fn new(name: impl Into<String>) -> Self {
+        Binary {
+            name: name.into(),
+            rename: None,
+        }
+    }
+
+    /// The file name used inside the archive
+    pub fn archive_name(&self) -> &str {
+        self.rename.as_deref().unwrap_or(&self.name)
+    }
+
+    /// The binary as built for the os, with its executable extension
+    pub fn for_os(&self, os: &Os) -> Binary {
+        let with_extension = |name: &str| match os.executable_extension() {
+            Some(extension) => format!("{}.{}", name, extension),
+            None => name.to_owned(),
+        };
+
+        Binary {
+            name: with_extension(&self.name),
+            rename: self.rename.as_deref().map(with_extension),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BinaryConfig {
+    Name(String),
+    Detailed {
+        name: String,
+        rename: Option<String>,
+    },
+}
+
+impl From<BinaryConfig> for Binary {
+    fn from(value: BinaryConfig) -> Self {
+        match value {
+            BinaryConfig::Name(name) => Binary::new(name),
+            BinaryConfig::Detailed { name, rename } => Binary { name, rename },
+        }
+    }
+}
+
+/// Accepts either a single binary or a list of binaries
+pub fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<Binary>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Binary),
+        Many(Vec<Binary>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(binary) => Ok(vec![binary]),
+        OneOrMany::Many(binaries) => Ok(binaries),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Wrapper {
+        #[serde(deserialize_with = "one_or_many")]
+        binary: Vec<Binary>,
+    }
+
+    #[test]
+    fn should_deserialize_a_single_binary() {
+        let wrapper: Wrapper = serde_yaml::from_str("binary: tool").unwrap();
+
+        assert_eq!(wrapper.binary, vec![Binary::new("tool")]);
+    }
+
+    #[test]
+    fn should_deserialize_a_list_of_binaries() {
+        let yaml = r#"
+binary:
+  - tool
+  - name: tool-daemon
+    rename: toold
+"#;
+        let wrapper: Wrapper = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(wrapper.binary[0].archive_name(), "tool");
+        assert_eq!(wrapper.binary[1].name, "tool-daemon");
+        assert_eq!(wrapper.binary[1].archive_name(), "toold");
+    }
+
+    #[test]
+    fn should_add_the_executable_extension_on_windows() {
+        let binary = Binary {
+            name: "tool-daemon".to_string(),
+            rename: Some("toold".to_string()),
+        };
+
+        let windows = binary.for_os(&Os::PcWindowsMsvc);
+        let linux = binary.for_os(&Os::UnknownLinuxGnu);
+
+        assert_eq!(windows.name, "tool-daemon.exe");
+        assert_eq!(windows.archive_name(), "toold.exe");
+        assert_eq!(linux, binary);
+    }
+}
@@ -0,0 +1,64 @@
+use super::{arch::Arch, os::Os};
+use serde::{Deserialize, Serialize};
+
+/// A build matrix filter. Every field that is set must match the entry
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatrixRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<Arch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<Os>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abi: Option<String>,
+}
+
+impl MatrixRule {
+    pub fn matches(&self, arch: &Arch, os: &Os) -> bool {
+        let arch_matches = self.arch.as_ref().is_none_or(|a| a == arch);
+        let os_matches = self.os.as_ref().is_none_or(|o| o == os);
+        let abi_matches = self
+            .abi
+            .as_ref()
+            .is_none_or(|abi| os.abi() == Some(abi.as_str()));
+
+        arch_matches && os_matches && abi_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_match_everything_when_empty() {
+        let rule = MatrixRule::default();
+
+        assert!(rule.matches(&Arch::Amd64, &Os::UnknownLinuxGnu));
+        assert!(rule.matches(&Arch::Arm, &Os::AppleDarwin));
+    }
+
+    #[test]
+    fn should_match_only_the_given_fields() {
+        let rule = MatrixRule {
+            arch: Some(Arch::Arm),
+            os: Some(Os::AppleDarwin),
+            abi: None,
+        };
+
+        assert!(rule.matches(&Arch::Arm, &Os::AppleDarwin));
+        assert!(!rule.matches(&Arch::Arm, &Os::UnknownLinuxGnu));
+        assert!(!rule.matches(&Arch::Amd64, &Os::AppleDarwin));
+    }
+
+    #[test]
+    fn should_match_abi() {
+        let rule = MatrixRule {
+            abi: Some("musl".to_string()),
+            ..Default::default()
+        };
+
+        assert!(rule.matches(&Arch::Amd64, &Os::UnknownLinuxMusl));
+        assert!(!rule.matches(&Arch::Amd64, &Os::UnknownLinuxGnu));
+        assert!(!rule.matches(&Arch::Amd64, &Os::AppleDarwin));
+    }
+}
@@ -1,19 +1,63 @@
 pub mod arch;
+pub mod binary;
+pub mod matrix_rule;
 pub mod os;
 pub mod prebuilt;
+pub mod strip;
 
-use self::prebuilt::PreBuiltAsset;
+use self::{binary::Binary, matrix_rule::MatrixRule, prebuilt::PreBuiltAsset, strip::Strip};
 use arch::Arch;
 use os::Os;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+const DEFAULT_TARGET_DIR: &str = "target";
+const DEFAULT_DIST_DIR: &str = "dist";
+const RELEASE_DIR: &str = "release";
+const STRIPPED_DIR_NAME: &str = "stripped";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Build {
     pub arch: Option<Vec<Arch>>,
     pub os: Option<Vec<Os>>,
-    pub binary: String,
+    /// Defaults to the `[[bin]]` targets of the package
+    #[serde(default, alias = "binaries", deserialize_with = "binary::one_or_many")]
+    pub binary: Vec<Binary>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prebuilt: Option<Vec<PreBuiltAsset>>,
+    /// When set, only the arch/os combinations matching at least one rule are built
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<MatrixRule>,
+    /// Arch/os combinations matching any of these rules are never built
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<MatrixRule>,
+    /// The rustup toolchain to build with, such as `+nightly`. Defaults to `rust-toolchain.toml`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toolchain: Option<String>,
+    /// Never installs missing rustup targets
+    #[serde(default)]
+    pub offline: bool,
+    /// Builds and archives with fixed dates and paths, so the artifacts are bit-for-bit identical
+    #[serde(default)]
+    pub reproducible: bool,
+    /// Fails when a musl binary is dynamically linked
+    #[serde(default)]
+    pub static_musl: bool,
+    /// Merges the x86_64 and aarch64 darwin binaries into a universal binary
+    #[serde(default)]
+    pub universal: bool,
+    /// Always rebuilds, ignoring the binaries cached in the output directory
+    #[serde(default)]
+    pub no_cache: bool,
+    /// Strips the built binaries, optionally keeping their debug info apart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip: Option<Strip>,
+    /// The output directory. Defaults to `dist`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dist: Option<PathBuf>,
+    /// The cargo target directory, as reported by `cargo metadata`
+    #[serde(skip)]
+    pub target_dir: Option<PathBuf>,
 }
 
 #[derive(PartialEq, PartialOrd)]
@@ -24,6 +68,59 @@ pub enum TargetType {
 }
 
 impl Build {
+    /// The name used for the release assets, taken from the first binary
+    pub fn name(&self) -> &str {
+        self.binary
+            .first()
+            .map(|binary| binary.name.as_str())
+            .unwrap_or_default()
+    }
+
+    /// The name of the first binary inside the archives
+    pub fn binary_name(&self) -> &str {
+        self.binary
+            .first()
+            .map(Binary::archive_name)
+            .unwrap_or_default()
+    }
+
+    /// The binaries file names on the os
+    pub fn binaries(&self, os: &Os) -> Vec<Binary> {
+        self.binary.iter().map(|binary| binary.for_os(os)).collect()
+    }
+
+    pub fn dist_dir(&self) -> &Path {
+        self.dist.as_deref().unwrap_or(Path::new(DEFAULT_DIST_DIR))
+    }
+
+    pub fn target_dir(&self) -> &Path {
+        self.target_dir
+            .as_deref()
+            .unwrap_or(Path::new(DEFAULT_TARGET_DIR))
+    }
+
+    /// The directory holding the release binaries of a target, or of the host
+    pub fn release_dir(&self, target: Option<&str>) -> PathBuf {
+        let target_dir = self.target_dir();
+
+        match target {
+            Some(target) => target_dir.join(target).join(RELEASE_DIR),
+            None => target_dir.join(RELEASE_DIR),
+        }
+    }
+
+    /// The directory holding the released binaries of a target, or of the host: the stripped
+    /// copies in the dist dir when stripping, so the cargo output is never modified
+    pub fn binaries_dir(&self, target: Option<&str>) -> PathBuf {
+        match self.strip {
+            Some(_) => self
+                .dist_dir()
+                .join(STRIPPED_DIR_NAME)
+                .join(target.unwrap_or("host")),
+            None => self.release_dir(target),
+        }
+    }
+
     pub fn target_type(&self) -> TargetType {
         if self.has_prebuilt() {
             TargetType::PreBuilt
@@ -57,20 +154,30 @@ impl Build {
     fn has_prebuilt(&self) -> bool {
         self.prebuilt.is_some() && !self.prebuilt.as_ref().unwrap().is_empty()
     }
+
+    /// Checks the include/ignore rules for an arch/os combination
+    pub fn is_allowed(&self, arch: &Arch, os: &Os) -> bool {
+        let included =
+            self.include.is_empty() || self.include.iter().any(|rule| rule.matches(arch, os));
+        let ignored = self.ignore.iter().any(|rule| rule.matches(arch, os));
+
+        included && !ignored
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{arch::Arch, os::Os};
+    use super::{arch::Arch, binary::Binary, os::Os};
     use crate::build::Build;
 
     #[test]
     fn should_validate_if_multi_target() {
         let build = Build {
-            binary: "binary".to_string(),
+            binary: vec![Binary::new("binary")],
             arch: Some(vec![Arch::Amd64]),
             os: Some(vec![Os::UnknownLinuxGnu]),
             prebuilt: None,
+            ..Default::default()
         };
 
         assert!(build.is_multi_target());
@@ -79,10 +186,11 @@ mod tests {
     #[test]
     fn should_validate_id_single_target() {
         let build = Build {
-            binary: "binary".to_string(),
+            binary: vec![Binary::new("binary")],
             arch: None,
             os: None,
             prebuilt: None,
+            ..Default::default()
         };
 
         assert!(!build.is_multi_target());
@@ -91,10 +199,11 @@ mod tests {
     #[test]
     fn should_validate_if_multi_arch() {
         let build = Build {
-            binary: "binary".to_string(),
+            binary: vec![Binary::new("binary")],
             arch: Some(vec![Arch::Amd64]),
             os: None,
             prebuilt: None,
+            ..Default::default()
         };
 
         assert!(build.is_multi_arch());
@@ -103,10 +212,11 @@ mod tests {
     #[test]
     fn should_validate_if_single_arch() {
         let build = Build {
-            binary: "binary".to_string(),
+            binary: vec![Binary::new("binary")],
             arch: None,
             os: None,
             prebuilt: None,
+            ..Default::default()
         };
 
         assert!(!build.is_multi_arch());
@@ -115,10 +225,11 @@ mod tests {
     #[test]
     fn should_validate_if_multi_os() {
         let build = Build {
-            binary: "binary".to_string(),
+            binary: vec![Binary::new("binary")],
             arch: None,
             os: Some(vec![Os::UnknownLinuxGnu]),
             prebuilt: None,
+            ..Default::default()
         };
 
         assert!(build.is_multi_os());
@@ -127,10 +238,11 @@ mod tests {
     #[test]
     fn should_validate_if_single_os() {
         let build = Build {
-            binary: "binary".to_string(),
+            binary: vec![Binary::new("binary")],
             arch: None,
             os: None,
             prebuilt: None,
+            ..Default::default()
         };
 
         assert!(!build.is_multi_os());
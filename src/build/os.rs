@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Os {
@@ -6,6 +7,53 @@ pub enum Os {
     AppleDarwin,
     #[serde(rename(deserialize = "linux"))]
     UnknownLinuxGnu,
+    #[serde(rename(deserialize = "linux-musl"))]
+    UnknownLinuxMusl,
+    #[serde(rename(deserialize = "windows"))]
+    PcWindowsMsvc,
+}
+
+impl Os {
+    /// The os this binary was built for
+    pub fn host() -> Os {
+        if cfg!(target_os = "macos") {
+            Os::AppleDarwin
+        } else if cfg!(target_os = "windows") {
+            Os::PcWindowsMsvc
+        } else if cfg!(target_env = "musl") {
+            Os::UnknownLinuxMusl
+        } else {
+            Os::UnknownLinuxGnu
+        }
+    }
+
+    /// The vendor, os and ABI part of the target triple
+    pub fn target(&self) -> &str {
+        match self {
+            Os::AppleDarwin => "apple-darwin",
+            Os::UnknownLinuxGnu => "unknown-linux-gnu",
+            Os::UnknownLinuxMusl => "unknown-linux-musl",
+            Os::PcWindowsMsvc => "pc-windows-msvc",
+        }
+    }
+
+    /// The target ABI, if the os has more than one
+    pub fn abi(&self) -> Option<&str> {
+        match self {
+            Os::AppleDarwin => None,
+            Os::UnknownLinuxGnu => Some("gnu"),
+            Os::UnknownLinuxMusl => Some("musl"),
+            Os::PcWindowsMsvc => Some("msvc"),
+        }
+    }
+
+    /// The extension of the executables, if the os uses one
+    pub fn executable_extension(&self) -> Option<&str> {
+        match self {
+            Os::PcWindowsMsvc => Some("exe"),
+            _ => None,
+        }
+    }
 }
 
 impl From<String> for Os {
@@ -13,16 +61,20 @@ impl From<String> for Os {
         match value.to_lowercase().as_str() {
             "apple-darwin" | "darwin" | "macos" => Os::AppleDarwin,
             "unknown-linux-gnu" | "linux" => Os::UnknownLinuxGnu,
+            "unknown-linux-musl" | "linux-musl" => Os::UnknownLinuxMusl,
+            "pc-windows-msvc" | "windows" => Os::PcWindowsMsvc,
             _ => panic!("Unknown arch"),
         }
     }
 }
 
-impl ToString for Os {
-    fn to_string(&self) -> String {
+impl Display for Os {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Os::AppleDarwin => "apple".to_string(),
-            Os::UnknownLinuxGnu => "linux".to_string(),
+            Os::AppleDarwin => write!(f, "apple"),
+            Os::UnknownLinuxGnu => write!(f, "linux"),
+            Os::UnknownLinuxMusl => write!(f, "linux-musl"),
+            Os::PcWindowsMsvc => write!(f, "windows"),
         }
     }
 }
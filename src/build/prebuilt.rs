@@ -1,4 +1,5 @@
 use super::{arch::Arch, os::Os};
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -10,3 +11,16 @@ pub struct PreBuiltAsset {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub os: Option<Os>,
 }
+
+impl PreBuiltAsset {
+    /// The arch and os of the binary, which every prebuilt binary must set
+    pub fn arch_os(&self) -> Result<(&Arch, &Os)> {
+        match (&self.arch, &self.os) {
+            (Some(arch), Some(os)) => Ok((arch, os)),
+            _ => bail!(anyhow::anyhow!(
+                "the prebuilt binary {} needs both an arch and an os",
+                self.path.display()
+            )),
+        }
+    }
+}
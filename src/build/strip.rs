@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_OBJCOPY_BIN_NAME: &str = "objcopy";
+const DEFAULT_STRIP_BIN_NAME: &str = "strip";
+
+/// Strips the debug info from the release binaries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Strip {
+    /// Keeps the debug info as separate artifacts, uploaded next to the archives
+    #[serde(default)]
+    pub split_debug_info: bool,
+    /// The objcopy binary, such as `llvm-objcopy` when stripping binaries of other platforms
+    #[serde(default = "Strip::default_objcopy")]
+    pub objcopy: String,
+    /// The strip binary of the darwin binaries, such as `llvm-strip` when stripping them on linux
+    #[serde(default = "Strip::default_strip")]
+    pub strip: String,
+}
+
+impl Strip {
+    fn default_objcopy() -> String {
+        DEFAULT_OBJCOPY_BIN_NAME.to_owned()
+    }
+
+    fn default_strip() -> String {
+        DEFAULT_STRIP_BIN_NAME.to_owned()
+    }
+}
+
+impl Default for Strip {
+    fn default() -> Self {
+        Strip {
+            split_debug_info: false,
+            objcopy: Strip::default_objcopy(),
+            strip: Strip::default_strip(),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The prefixes, in any case, of the files bundled by default from the crate root
+const DOC_FILE_PREFIXES: [&str; 5] = ["LICENSE", "LICENCE", "COPYING", "README", "CHANGELOG"];
+const LICENSE_FILE_PREFIXES: [&str; 3] = ["LICENSE", "LICENCE", "COPYING"];
+
+/// The license, readme and changelog files found in the directory, sorted by name
+pub fn doc_files(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut files = vec![];
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && has_prefix(&path, &DOC_FILE_PREFIXES) {
+            files.push(path.strip_prefix(dir).unwrap_or(&path).to_owned());
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Whether the file is named like a license
+pub fn is_license(path: &Path) -> bool {
+    has_prefix(path, &LICENSE_FILE_PREFIXES)
+}
+
+fn has_prefix(path: &Path, prefixes: &[&str]) -> bool {
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy().to_uppercase();
+
+    prefixes.iter().any(|prefix| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn should_find_the_doc_files_in_any_case() {
+        let dir = TempDir::new("bundle").unwrap();
+        for name in [
+            "LICENSE-MIT",
+            "Licence.txt",
+            "COPYING",
+            "Readme.md",
+            "changelog.md",
+            "Cargo.toml",
+        ] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        fs::create_dir(dir.path().join("README")).unwrap();
+
+        assert_eq!(
+            doc_files(dir.path()).unwrap(),
+            vec![
+                PathBuf::from("COPYING"),
+                PathBuf::from("LICENSE-MIT"),
+                PathBuf::from("Licence.txt"),
+                PathBuf::from("Readme.md"),
+                PathBuf::from("changelog.md"),
+            ]
+        );
+        assert!(is_license(Path::new("docs/COPYING")));
+        assert!(!is_license(Path::new("README.md")));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::build::{arch::Arch, os::Os, Build};
+use std::fmt::Display;
 
 #[derive(Debug, Clone)]
 pub struct ArchOsMatrixEntry {
@@ -15,9 +16,15 @@ impl ArchOsMatrixEntry {
     }
 }
 
-impl ToString for ArchOsMatrixEntry {
-    fn to_string(&self) -> String {
-        format!("{}-{}", self.arch.to_string(), self.os.to_string())
+/// Displays the rustc target triple
+impl Display for ArchOsMatrixEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.arch, &self.os) {
+            (Arch::Arm, Os::UnknownLinuxGnu | Os::UnknownLinuxMusl) => {
+                write!(f, "{}-{}eabihf", self.arch, self.os.target())
+            }
+            _ => write!(f, "{}-{}", self.arch, self.os.target()),
+        }
     }
 }
 
@@ -29,7 +36,13 @@ impl From<Build> for Vec<ArchOsMatrixEntry> {
             if let Some(oss) = &build.os {
                 for arch in archs {
                     for os in oss {
-                        matrix.push(ArchOsMatrixEntry::new(arch, os));
+                        let entry = ArchOsMatrixEntry::new(arch, os);
+
+                        if build.is_allowed(arch, os) {
+                            matrix.push(entry);
+                        } else {
+                            log::info!("skipping {} due to the build matrix rules", entry);
+                        }
                     }
                 }
             }
@@ -38,3 +51,64 @@ impl From<Build> for Vec<ArchOsMatrixEntry> {
         matrix
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{binary::Binary, matrix_rule::MatrixRule};
+
+    fn build(include: Vec<MatrixRule>, ignore: Vec<MatrixRule>) -> Build {
+        Build {
+            binary: vec![Binary::new("binary")],
+            arch: Some(vec![Arch::Amd64, Arch::Arm, Arch::Arm64]),
+            os: Some(vec![Os::AppleDarwin, Os::UnknownLinuxGnu]),
+            include,
+            ignore,
+            ..Default::default()
+        }
+    }
+
+    fn targets(matrix: Vec<ArchOsMatrixEntry>) -> Vec<String> {
+        matrix.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn should_create_the_full_matrix_without_rules() {
+        let matrix = Vec::<ArchOsMatrixEntry>::from(build(vec![], vec![]));
+
+        assert_eq!(matrix.len(), 6);
+    }
+
+    #[test]
+    fn should_skip_ignored_entries() {
+        let ignore = vec![MatrixRule {
+            arch: Some(Arch::Arm),
+            os: Some(Os::AppleDarwin),
+            abi: None,
+        }];
+
+        let matrix = Vec::<ArchOsMatrixEntry>::from(build(vec![], ignore));
+
+        assert_eq!(matrix.len(), 5);
+        assert!(!targets(matrix).contains(&"arm-apple-darwin".to_string()));
+    }
+
+    #[test]
+    fn should_keep_only_included_entries() {
+        let include = vec![MatrixRule {
+            os: Some(Os::UnknownLinuxGnu),
+            ..Default::default()
+        }];
+        let ignore = vec![MatrixRule {
+            arch: Some(Arch::Arm),
+            ..Default::default()
+        }];
+
+        let matrix = Vec::<ArchOsMatrixEntry>::from(build(include, ignore));
+
+        assert_eq!(
+            targets(matrix),
+            vec!["x86_64-unknown-linux-gnu", "aarch64-unknown-linux-gnu"]
+        );
+    }
+}
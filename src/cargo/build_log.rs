@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::VecDeque,
+    path::Path,
+    process::{ExitStatus, Stdio},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    process::Command,
+    sync::Mutex,
+};
+
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Output of a build whose logs were captured to a file
+pub struct BuildOutput {
+    pub status: ExitStatus,
+    pub stderr_tail: Vec<String>,
+}
+
+/// The log file of a target, `host` being the target of a single target build
+pub fn log_file_name(target: Option<&str>) -> String {
+    format!("{}.log", target.unwrap_or("host"))
+}
+
+/// Runs the command, writing its output to the log file
+/// and streaming it at the debug level with the label as prefix
+pub async fn run(command: &mut Command, log_path: &Path, label: &str) -> Result<BuildOutput> {
+    if let Some(dir) = log_path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let log = Mutex::new(
+        File::create(log_path)
+            .await
+            .with_context(|| format!("Cannot create {}", log_path.display()))?,
+    );
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().context("Cannot capture the stdout")?;
+    let stderr = child.stderr.take().context("Cannot capture the stderr")?;
+
+    let (stdout, stderr) = tokio::join!(pipe(stdout, &log, label), pipe(stderr, &log, label));
+    stdout?;
+    let stderr_tail = stderr?.into_iter().collect();
+
+    let status = child.wait().await?;
+    log.lock().await.flush().await?;
+
+    Ok(BuildOutput {
+        status,
+        stderr_tail,
+    })
+}
+
+/// Copies the lines to the log, keeping the last ones. Lines that are not valid UTF-8 are
+/// decoded lossily, so the output alone never fails the build
+async fn pipe(
+    reader: impl AsyncRead + Unpin,
+    log: &Mutex<File>,
+    label: &str,
+) -> Result<VecDeque<String>> {
+    let mut reader = BufReader::new(reader);
+    let mut buffer = vec![];
+    let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+
+    while reader.read_until(b'\n', &mut buffer).await? > 0 {
+        let line = String::from_utf8_lossy(&buffer)
+            .trim_end_matches(['\n', '\r'])
+            .to_owned();
+        buffer.clear();
+
+        log::debug!("[{}] {}", label, line);
+        log.lock()
+            .await
+            .write_all(format!("{}\n", line).as_bytes())
+            .await?;
+
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+
+    Ok(tail)
+}
+
+/// Writes the log of a target restored from the build cache instead of built
+pub fn write_restored(log_path: &Path, label: &str, fingerprint: &str) -> Result<()> {
+    if let Some(dir) = log_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(
+        log_path,
+        format!(
+            "{} was restored from the build cache, fingerprint {}\n",
+            label, fingerprint
+        ),
+    )
+    .with_context(|| format!("Cannot create {}", log_path.display()))
+}
+
+/// The error message of a failed build, ending with the last lines it wrote
+pub fn failure_message(label: &str, log_path: &Path, stderr_tail: &[String]) -> String {
+    format!(
+        "Build failed for {} (full log at {}):\n{}",
+        label,
+        log_path.display(),
+        stderr_tail.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn should_capture_the_output_and_keep_the_stderr_tail() {
+        let dir = TempDir::new("build_log").unwrap();
+        let log_path = dir.path().join("logs").join(log_file_name(None));
+
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "echo compiling; for i in $(seq 1 30); do echo error $i >&2; done; exit 1",
+        ]);
+
+        let output = run(&mut command, &log_path, "host").await.unwrap();
+
+        assert!(!output.status.success());
+        assert_eq!(output.stderr_tail.len(), STDERR_TAIL_LINES);
+        assert_eq!(output.stderr_tail.last().unwrap(), "error 30");
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.contains("compiling\n"));
+        assert!(log.contains("error 1\n"));
+        assert!(log.contains("error 30\n"));
+    }
+
+    #[tokio::test]
+    async fn should_capture_output_that_is_not_utf8() {
+        let dir = TempDir::new("build_log").unwrap();
+        let log_path = dir.path().join(log_file_name(None));
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "printf 'caf\\351\\n' >&2; echo done"]);
+
+        let output = run(&mut command, &log_path, "host").await.unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stderr_tail, vec!["caf\u{fffd}".to_owned()]);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.contains("done\n"));
+    }
+}
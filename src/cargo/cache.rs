@@ -0,0 +1,172 @@
+use super::arch_os_matrix::ArchOsMatrixEntry;
+use crate::build::{os::Os, Build};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+const CACHE_DIR_NAME: &str = "cache";
+const FINGERPRINT_FILE_NAME: &str = "fingerprint";
+const FLAG_ENV_VARS: [&str; 3] = [
+    "RUSTFLAGS",
+    "CARGO_BUILD_RUSTFLAGS",
+    "CARGO_ENCODED_RUSTFLAGS",
+];
+
+/// Binaries of previous builds, reused while the commit, flags and toolchain are unchanged
+pub struct BuildCache {
+    dir: PathBuf,
+    key: Vec<String>,
+}
+
+impl BuildCache {
+    pub fn new(
+        dist_dir: impl AsRef<Path>,
+        commit_sha: &str,
+        build: &Build,
+        toolchain_version: &str,
+    ) -> Self {
+        let mut key = vec![
+            format!("commit={}", commit_sha),
+            format!("toolchain={}", toolchain_version),
+            format!("reproducible={}", build.reproducible),
+            format!(
+                "binaries={}",
+                build
+                    .binary
+                    .iter()
+                    .map(|binary| binary.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        ];
+        for var in FLAG_ENV_VARS {
+            key.push(format!("{}={}", var, env::var(var).unwrap_or_default()));
+        }
+
+        BuildCache {
+            dir: dist_dir.as_ref().join(CACHE_DIR_NAME),
+            key,
+        }
+    }
+
+    /// The fingerprint of the target binaries, identifying their cache entry
+    pub fn fingerprint(&self, target: Option<&ArchOsMatrixEntry>) -> String {
+        let target = target.map(|entry| entry.to_string());
+        self.target_fingerprint(target.as_deref())
+    }
+
+    fn target_fingerprint(&self, target: Option<&str>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("target={}\n", target.unwrap_or("host")));
+        for line in &self.key {
+            hasher.update(line);
+            hasher.update("\n");
+        }
+
+        hex::encode(hasher.finalize())
+    }
+
+    fn target_dir(&self, target: Option<&str>) -> PathBuf {
+        self.dir.join(target.unwrap_or("host"))
+    }
+
+    /// Copies the cached binaries of the target to its release directory, if still up to date
+    pub fn restore(&self, build: &Build, target: Option<&ArchOsMatrixEntry>) -> Result<bool> {
+        let binaries = build.binaries(&target_os(target));
+        let target = target.map(|entry| entry.to_string());
+        let target = target.as_deref();
+        let dir = self.target_dir(target);
+        let fingerprint = fs::read_to_string(dir.join(FINGERPRINT_FILE_NAME)).unwrap_or_default();
+
+        if fingerprint != self.target_fingerprint(target)
+            || binaries
+                .iter()
+                .any(|binary| !dir.join(&binary.name).is_file())
+        {
+            return Ok(false);
+        }
+
+        let release_dir = build.release_dir(target);
+        fs::create_dir_all(&release_dir)?;
+        for binary in &binaries {
+            fs::copy(dir.join(&binary.name), release_dir.join(&binary.name))
+                .with_context(|| format!("Cannot restore {} from the cache", binary.name))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Keeps the freshly built binaries of the target, along with their fingerprint
+    pub fn store(&self, build: &Build, target: Option<&ArchOsMatrixEntry>) -> Result<()> {
+        let binaries = build.binaries(&target_os(target));
+        let target = target.map(|entry| entry.to_string());
+        let target = target.as_deref();
+        let dir = self.target_dir(target);
+        let fingerprint_path = dir.join(FINGERPRINT_FILE_NAME);
+        if fingerprint_path.exists() {
+            fs::remove_file(&fingerprint_path)?;
+        }
+        fs::create_dir_all(&dir)?;
+
+        let release_dir = build.release_dir(target);
+        for binary in &binaries {
+            fs::copy(release_dir.join(&binary.name), dir.join(&binary.name))
+                .with_context(|| format!("Cannot cache {}", binary.name))?;
+        }
+
+        // written last, so an interrupted store is never trusted
+        fs::write(fingerprint_path, self.target_fingerprint(target))?;
+
+        Ok(())
+    }
+}
+
+fn target_os(target: Option<&ArchOsMatrixEntry>) -> Os {
+    target
+        .map(|entry| entry.os.to_owned())
+        .unwrap_or_else(Os::host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{arch::Arch, binary::Binary};
+    use tempdir::TempDir;
+
+    #[test]
+    fn should_restore_only_matching_fingerprints() {
+        let dir = TempDir::new("cache").unwrap();
+        let build = Build {
+            binary: vec![Binary::new("tool")],
+            target_dir: Some(dir.path().join("target")),
+            ..Default::default()
+        };
+        let entry = ArchOsMatrixEntry::new(&Arch::Amd64, &Os::UnknownLinuxGnu);
+        let target = Some(&entry);
+        let release_dir = build.release_dir(Some("x86_64-unknown-linux-gnu"));
+        fs::create_dir_all(&release_dir).unwrap();
+        fs::write(release_dir.join("tool"), "binary").unwrap();
+
+        let cache = BuildCache::new(dir.path(), "abc", &build, "rustc 1.0.0");
+        assert!(!cache.restore(&build, target).unwrap());
+
+        cache.store(&build, target).unwrap();
+        fs::remove_file(release_dir.join("tool")).unwrap();
+
+        assert!(cache.restore(&build, target).unwrap());
+        assert_eq!(
+            fs::read_to_string(release_dir.join("tool")).unwrap(),
+            "binary"
+        );
+        assert!(!cache.restore(&build, None).unwrap());
+
+        let other_commit = BuildCache::new(dir.path(), "def", &build, "rustc 1.0.0");
+        assert!(!other_commit.restore(&build, target).unwrap());
+
+        let other_toolchain = BuildCache::new(dir.path(), "abc", &build, "rustc 1.1.0");
+        assert!(!other_toolchain.restore(&build, target).unwrap());
+    }
+}
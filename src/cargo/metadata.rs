@@ -0,0 +1,288 @@
+use super::DEFAULT_CARGO_BIN_NAME;
+use crate::build::binary::Binary;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tokio::process::Command;
+
+const BIN_TARGET_KIND: &str = "bin";
+const GITHUB_HOST: &str = "github.com";
+
+/// The subset of `cargo metadata --format-version 1` output used by the release
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<Package>,
+    pub target_directory: PathBuf,
+    /// The dependency graph, missing with `--no-deps`
+    pub resolve: Option<Resolve>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub license: Option<String>,
+    pub license_file: Option<PathBuf>,
+    pub repository: Option<String>,
+    pub manifest_path: PathBuf,
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Resolve {
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Node {
+    pub id: String,
+    pub deps: Vec<NodeDep>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NodeDep {
+    pub pkg: String,
+    pub dep_kinds: Vec<DepKind>,
+}
+
+/// The kind of a dependency, `None` for a normal one
+#[derive(Debug, Deserialize)]
+pub struct DepKind {
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub kind: Vec<String>,
+}
+
+pub async fn metadata() -> Result<Metadata> {
+    run(&["--no-deps"]).await
+}
+
+/// The metadata along with the dependency graph, restricted to the target when given
+pub async fn dependency_metadata(target: Option<&str>) -> Result<Metadata> {
+    match target {
+        Some(target) => run(&["--filter-platform", target]).await,
+        None => run(&[]).await,
+    }
+}
+
+async fn run(args: &[&str]) -> Result<Metadata> {
+    let output = Command::new(DEFAULT_CARGO_BIN_NAME)
+        .args(["metadata", "--format-version", "1"])
+        .args(args)
+        .output()
+        .await
+        .context("Cannot run cargo metadata")?;
+
+    if !output.status.success() {
+        bail!(anyhow::anyhow!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout).context("Cannot parse cargo metadata output")
+}
+
+impl Metadata {
+    /// The package whose manifest lives in `dir`, or the only package of the workspace
+    pub fn package_at(&self, dir: impl AsRef<Path>) -> Option<&Package> {
+        let manifest = dir.as_ref().join(super::DEFAULT_CARGO_FILE_NAME);
+
+        let only_package = match self.packages.as_slice() {
+            [package] => Some(package),
+            _ => None,
+        };
+
+        self.packages
+            .iter()
+            .find(|package| package.manifest_path == manifest)
+            .or(only_package)
+    }
+
+    /// The packages the given one depends on at run time, directly or not
+    pub fn normal_dependencies(&self, package: &Package) -> Vec<&Package> {
+        let Some(resolve) = &self.resolve else {
+            return vec![];
+        };
+        let nodes: HashMap<&str, &Node> = resolve
+            .nodes
+            .iter()
+            .map(|node| (node.id.as_str(), node))
+            .collect();
+
+        let mut seen = HashSet::from([package.id.as_str()]);
+        let mut pending = vec![package.id.as_str()];
+        while let Some(id) = pending.pop() {
+            let Some(node) = nodes.get(id) else {
+                continue;
+            };
+            for dep in &node.deps {
+                let normal = dep.dep_kinds.iter().any(|kind| kind.kind.is_none());
+                if normal && seen.insert(dep.pkg.as_str()) {
+                    pending.push(dep.pkg.as_str());
+                }
+            }
+        }
+
+        self.packages
+            .iter()
+            .filter(|dependency| {
+                dependency.id != package.id && seen.contains(dependency.id.as_str())
+            })
+            .collect()
+    }
+}
+
+impl Package {
+    pub fn binaries(&self) -> Vec<Binary> {
+        self.targets
+            .iter()
+            .filter(|target| target.kind.iter().any(|kind| kind == BIN_TARGET_KIND))
+            .map(|target| Binary::new(&target.name))
+            .collect()
+    }
+
+    /// Owner and name of the repository, when it is hosted on github
+    pub fn github_repository(&self) -> Option<(String, String)> {
+        let url = self.repository.as_ref()?;
+        let path = url
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .split_once(GITHUB_HOST)?
+            .1
+            .trim_start_matches([':', '/']);
+
+        match path.split('/').collect::<Vec<_>>().as_slice() {
+            [owner, repo] if !owner.is_empty() && !repo.is_empty() => {
+                Some((owner.to_string(), repo.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{
+        "packages": [
+            {
+                "id": "tool 1.0.0",
+                "name": "tool",
+                "version": "1.0.0",
+                "description": "A tool",
+                "homepage": null,
+                "license": "MIT",
+                "repository": "https://github.com/owner/tool.git",
+                "manifest_path": "/work/tool/Cargo.toml",
+                "targets": [
+                    { "name": "tool", "kind": ["lib"] },
+                    { "name": "tool", "kind": ["bin"] },
+                    { "name": "tool-daemon", "kind": ["bin"] }
+                ]
+            }
+        ],
+        "target_directory": "/work/tool/target"
+    }"#;
+
+    fn package() -> Package {
+        let metadata: Metadata = serde_json::from_str(METADATA).unwrap();
+        metadata.packages[0].to_owned()
+    }
+
+    #[test]
+    fn should_follow_only_the_normal_dependencies() {
+        let package = |name: &str| {
+            format!(
+                r#"{{ "id": "{0}", "name": "{0}", "version": "1.0.0", "manifest_path": "/{0}/Cargo.toml", "targets": [] }}"#,
+                name
+            )
+        };
+        let dep = |name: &str, kind: &str| {
+            format!(
+                r#"{{ "pkg": "{}", "dep_kinds": [{{ "kind": {} }}] }}"#,
+                name, kind
+            )
+        };
+        let json = format!(
+            r#"{{
+                "packages": [{}, {}, {}, {}, {}],
+                "target_directory": "/target",
+                "resolve": {{ "nodes": [
+                    {{ "id": "tool", "deps": [{}, {}, {}] }},
+                    {{ "id": "serde", "deps": [{}] }},
+                    {{ "id": "cc", "deps": [] }},
+                    {{ "id": "tempdir", "deps": [] }},
+                    {{ "id": "serde_derive", "deps": [] }}
+                ] }}
+            }}"#,
+            package("tool"),
+            package("serde"),
+            package("serde_derive"),
+            package("cc"),
+            package("tempdir"),
+            dep("serde", "null"),
+            dep("cc", r#""build""#),
+            dep("tempdir", r#""dev""#),
+            dep("serde_derive", "null"),
+        );
+        let metadata: Metadata = serde_json::from_str(&json).unwrap();
+
+        let names: Vec<&str> = metadata
+            .normal_dependencies(&metadata.packages[0])
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect();
+
+        assert_eq!(names, vec!["serde", "serde_derive"]);
+    }
+
+    #[test]
+    fn should_find_the_package_by_directory() {
+        let metadata: Metadata = serde_json::from_str(METADATA).unwrap();
+
+        let package = metadata.package_at("/work/tool").unwrap();
+
+        assert_eq!(package.name, "tool");
+    }
+
+    #[test]
+    fn should_list_only_bin_targets() {
+        let binaries = package().binaries();
+
+        assert_eq!(
+            binaries,
+            vec![Binary::new("tool"), Binary::new("tool-daemon")]
+        );
+    }
+
+    #[test]
+    fn should_parse_github_repository() {
+        let mut package = package();
+
+        assert_eq!(
+            package.github_repository(),
+            Some(("owner".to_string(), "tool".to_string()))
+        );
+
+        package.repository = Some("git@github.com:owner/tool".to_string());
+        assert_eq!(
+            package.github_repository(),
+            Some(("owner".to_string(), "tool".to_string()))
+        );
+
+        package.repository = Some("https://gitlab.com/owner/tool".to_string());
+        assert_eq!(package.github_repository(), None);
+    }
+}
@@ -1,59 +1,225 @@
-mod arch_os_matrix;
+pub mod arch_os_matrix;
+pub mod build_log;
+pub mod cache;
+pub mod metadata;
+mod reproducible;
+mod toolchain;
+pub mod vendor;
 
-use crate::build::{Build, TargetType};
+use crate::{
+    build::{os::Os, Build, TargetType},
+    checksum::Checksum,
+    cwd, git,
+};
 use anyhow::{bail, Result};
 use arch_os_matrix::ArchOsMatrixEntry;
-use std::{
-    path::{Path, PathBuf},
-    process::Stdio,
-};
+use cache::BuildCache;
+use reproducible::Reproducible;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tokio_stream::StreamExt;
+use toolchain::Toolchain;
 
 const DEFAULT_CARGO_FILE_NAME: &str = "Cargo.toml";
 const DEFAULT_CARGO_BIN_NAME: &str = "cargo";
 const DEFAULT_RUSTUP_BIN_NAME: &str = "rustup";
+const DEFAULT_RUSTC_BIN_NAME: &str = "rustc";
+const CARGO_TARGET_DIR: &str = "CARGO_TARGET_DIR";
+const REPRODUCIBLE_DIR_NAME: &str = "reproducible";
+const REPRODUCIBLE_RUNS: [&str; 2] = ["first", "second"];
+pub const LOGS_DIR_NAME: &str = "logs";
 
 pub async fn build(build: &Build) -> Result<()> {
     check_cargo()?;
     check_cargo_project()?;
+    let env = CargoEnv::new(build, cwd!(), None)?;
+    let cache = build_cache(build, &env).await?;
+    run_build(build, &env, cache.as_ref()).await
+}
+
+/// The cache of the build, unless disabled or the worktree has uncommitted changes
+async fn build_cache(build: &Build, env: &CargoEnv) -> Result<Option<BuildCache>> {
+    if build.no_cache {
+        return Ok(None);
+    }
+
+    let output_dirs = [build.dist_dir(), build.target_dir()];
+    let Some(commit_sha) = git::get_clean_head_sha(cwd!(), &output_dirs)? else {
+        log::info!("uncommitted or untracked changes, not using the build cache");
+        return Ok(None);
+    };
+    let toolchain_version = env.toolchain.version().await?;
+
+    Ok(Some(BuildCache::new(
+        build.dist_dir(),
+        &commit_sha,
+        build,
+        &toolchain_version,
+    )))
+}
+
+/// Restores the target binaries from the cache, telling whether the build can be skipped.
+///
+/// A restored target still gets its log, telling the fingerprint it was restored from
+pub fn restore_cached(
+    cache: Option<&BuildCache>,
+    build: &Build,
+    target: Option<&ArchOsMatrixEntry>,
+    log_path: &Path,
+) -> Result<bool> {
+    let Some(cache) = cache else {
+        return Ok(false);
+    };
+
+    let restored = cache.restore(build, target)?;
+    if restored {
+        let label = target.map_or("host".to_owned(), |entry| entry.to_string());
+        log::info!("{} is up to date, skipping its build", label);
+        build_log::write_restored(log_path, &label, &cache.fingerprint(target))?;
+    }
+
+    Ok(restored)
+}
+
+/// Builds the project twice into separate directories and compares the binaries
+pub async fn verify_reproducible(build: &Build) -> Result<()> {
+    check_cargo()?;
+    check_cargo_project()?;
+
+    if build.target_type() == TargetType::PreBuilt {
+        bail!(anyhow::anyhow!("prebuilt binaries cannot be rebuilt"));
+    }
+
+    let workspace = cwd!();
+    let dir = workspace.join(build.dist_dir()).join(REPRODUCIBLE_DIR_NAME);
+
+    let mut runs = vec![];
+    for run in REPRODUCIBLE_RUNS {
+        let target_dir = dir.join(run);
+        log::info!("Building into {}", target_dir.display());
+
+        let mut build = build.to_owned();
+        build.reproducible = true;
+        build.target_dir = Some(target_dir.to_owned());
+
+        let env = CargoEnv::new(&build, &workspace, Some(target_dir))?;
+        run_build(&build, &env, None).await?;
+
+        runs.push(binary_checksums(&build)?);
+    }
+
+    let mismatches = runs[0]
+        .iter()
+        .zip(runs[1].iter())
+        .filter(|((_, first), (_, second))| first.value() != second.value())
+        .inspect(|((name, first), (_, second))| {
+            log::error!(
+                "{} is not reproducible: {} != {}",
+                name,
+                first.value(),
+                second.value()
+            );
+        })
+        .count();
+
+    if mismatches > 0 {
+        bail!(anyhow::anyhow!(
+            "{} of {} binaries are not reproducible",
+            mismatches,
+            runs[0].len()
+        ));
+    }
+
+    log::info!("All {} binaries are reproducible", runs[0].len());
+    Ok(())
+}
+
+async fn run_build(build: &Build, env: &CargoEnv, cache: Option<&BuildCache>) -> Result<()> {
     match build.target_type() {
         TargetType::Multi => {
-            build_multi(Vec::from(build.to_owned())).await?;
+            let mut matrix = vec![];
+            for entry in Vec::<ArchOsMatrixEntry>::from(build.to_owned()) {
+                let log_path = env.log_path(Some(&entry.to_string()));
+                if !restore_cached(cache, build, Some(&entry), &log_path)? {
+                    matrix.push(entry);
+                }
+            }
+
+            let commands = build_multi(env, matrix).await?;
+            if let Some(cache) = cache {
+                for command in commands.iter().filter(|command| command.success) {
+                    cache.store(build, Some(&command.target))?;
+                }
+            }
+
+            let failures: Vec<String> = commands
+                .iter()
+                .filter(|command| !command.success)
+                .map(|command| {
+                    let target = command.target.to_string();
+                    build_log::failure_message(
+                        &target,
+                        &env.log_path(Some(&target)),
+                        &command.stderr_tail,
+                    )
+                })
+                .collect();
+
+            if !failures.is_empty() {
+                bail!(anyhow::anyhow!(
+                    "Cannot build every target\n{}",
+                    failures.join("\n")
+                ));
+            }
         }
         _ => {
-            build_single().await?;
+            if !restore_cached(cache, build, None, &env.log_path(None))? {
+                build_single(env).await?;
+                if let Some(cache) = cache {
+                    cache.store(build, None)?;
+                }
+            }
         }
     };
 
     Ok(())
 }
 
-pub async fn build_single() -> Result<()> {
-    Command::new(DEFAULT_CARGO_BIN_NAME)
-        .args(["build", "--release"])
-        .stderr(Stdio::null())
-        .stdout(Stdio::null())
-        .spawn()?
-        .wait()
-        .await?;
+pub async fn build_single(env: &CargoEnv) -> Result<()> {
+    let log_path = env.log_path(None);
+    let mut command = env.command();
+    command.args(["build", "--release"]);
+
+    let output = build_log::run(&mut command, &log_path, "host").await?;
+
+    if !output.status.success() {
+        bail!(anyhow::anyhow!(build_log::failure_message(
+            "host",
+            &log_path,
+            &output.stderr_tail
+        )));
+    }
 
     Ok(())
 }
 
-pub async fn build_multi(matrix: Vec<ArchOsMatrixEntry>) -> Result<Vec<CustomCommand>> {
-    for entry in &matrix {
-        check_cargo_target(entry).await?;
-    }
+pub async fn build_multi(
+    env: &CargoEnv,
+    matrix: Vec<ArchOsMatrixEntry>,
+) -> Result<Vec<CustomCommand>> {
+    let targets: Vec<String> = matrix.iter().map(|entry| entry.to_string()).collect();
+    env.toolchain.ensure_targets(&targets).await?;
 
-    let commands = create_commands(matrix).await?;
+    let commands = create_commands(env, matrix).await?;
 
     let mut stream = tokio_stream::iter(commands);
 
     let mut s = vec![];
     while let Some(mut c) = stream.next().await {
-        let exit_status = c.command.spawn()?.wait().await?;
-        if exit_status.success() {
+        let target = c.target.to_string();
+        let output = build_log::run(&mut c.command, &env.log_path(Some(&target)), &target).await?;
+        c.stderr_tail = output.stderr_tail;
+        if output.status.success() {
             log::info!("Build successful for {}", c.target.to_string());
             c.set_success();
         } else {
@@ -65,15 +231,15 @@ pub async fn build_multi(matrix: Vec<ArchOsMatrixEntry>) -> Result<Vec<CustomCom
     Ok(s)
 }
 
-async fn create_commands(matrix: Vec<ArchOsMatrixEntry>) -> Result<Vec<CustomCommand>> {
+async fn create_commands(
+    env: &CargoEnv,
+    matrix: Vec<ArchOsMatrixEntry>,
+) -> Result<Vec<CustomCommand>> {
     let commands = matrix.iter().map(|entry| {
         log::info!("creating build command for {}", entry.to_string());
 
-        let mut command = Command::new(DEFAULT_CARGO_BIN_NAME);
-        command
-            .args(["build", "-q", "--release", "--target", &entry.to_string()])
-            .stderr(Stdio::inherit())
-            .stdout(Stdio::inherit());
+        let mut command = env.command();
+        command.args(["build", "--release", "--target", &entry.to_string()]);
 
         CustomCommand::new(command, entry.to_owned())
     });
@@ -94,11 +260,98 @@ fn check_cargo() -> Result<()> {
     }
 }
 
-async fn check_cargo_target(target: &ArchOsMatrixEntry) -> Result<()> {
-    let mut command = Command::new(DEFAULT_RUSTUP_BIN_NAME);
-    command.args(["rustup", "target", "add", &target.to_string()]);
-    command.spawn()?.wait().await?;
-    Ok(())
+/// The built targets, `None` being the host target of a single target build
+pub fn targets(build: &Build) -> Vec<Option<ArchOsMatrixEntry>> {
+    match build.target_type() {
+        TargetType::Multi => Vec::<ArchOsMatrixEntry>::from(build.to_owned())
+            .into_iter()
+            .map(Some)
+            .collect(),
+        _ => vec![None],
+    }
+}
+
+/// Checksums of every built binary, labeled by target and binary name
+fn binary_checksums(build: &Build) -> Result<Vec<(String, Checksum)>> {
+    let mut checksums = vec![];
+    for target in targets(build) {
+        let os = target
+            .as_ref()
+            .map(|entry| entry.os.to_owned())
+            .unwrap_or_else(Os::host);
+        let target = target.map(|entry| entry.to_string());
+        for binary in &build.binaries(&os) {
+            let path = build.release_dir(target.as_deref()).join(&binary.name);
+            let name = match &target {
+                Some(target) => format!("{}/{}", target, binary.name),
+                None => binary.name.to_owned(),
+            };
+
+            checksums.push((name, Checksum::new(path)?));
+        }
+    }
+
+    Ok(checksums)
+}
+
+/// Settings shared by every cargo invocation of a build
+pub struct CargoEnv {
+    toolchain: Toolchain,
+    reproducible: Option<Reproducible>,
+    target_dir: Option<PathBuf>,
+    log_dir: PathBuf,
+}
+
+impl CargoEnv {
+    fn new(
+        build: &Build,
+        workspace: impl AsRef<Path>,
+        target_dir: Option<PathBuf>,
+    ) -> Result<Self> {
+        let workspace = workspace.as_ref();
+        let toolchain = Toolchain::new(build, workspace)?;
+        let reproducible = if build.reproducible {
+            let reproducible = Reproducible::new(workspace, target_dir.as_deref())?;
+            log::info!(
+                "Reproducible build, SOURCE_DATE_EPOCH={}",
+                reproducible.source_date_epoch()
+            );
+            Some(reproducible)
+        } else {
+            None
+        };
+
+        let log_dir = match &target_dir {
+            Some(target_dir) => target_dir.join(LOGS_DIR_NAME),
+            None => workspace.join(build.dist_dir()).join(LOGS_DIR_NAME),
+        };
+
+        Ok(CargoEnv {
+            toolchain,
+            reproducible,
+            target_dir,
+            log_dir,
+        })
+    }
+
+    fn log_path(&self, target: Option<&str>) -> PathBuf {
+        self.log_dir.join(build_log::log_file_name(target))
+    }
+
+    /// A cargo command running on the selected toolchain
+    fn command(&self) -> Command {
+        let mut command = Command::new(DEFAULT_CARGO_BIN_NAME);
+        if let Some(toolchain) = self.toolchain.cargo_arg() {
+            command.arg(toolchain);
+        }
+        if let Some(reproducible) = &self.reproducible {
+            reproducible.apply(&mut command);
+        }
+        if let Some(target_dir) = &self.target_dir {
+            command.env(CARGO_TARGET_DIR, target_dir);
+        }
+        command
+    }
 }
 
 fn check_cargo_project() -> Result<PathBuf> {
@@ -124,6 +377,7 @@ pub struct CustomCommand {
     command: Command,
     target: ArchOsMatrixEntry,
     success: bool,
+    stderr_tail: Vec<String>,
 }
 
 impl CustomCommand {
@@ -132,6 +386,7 @@ impl CustomCommand {
             command,
             target,
             success: false,
+            stderr_tail: vec![],
         }
     }
 
@@ -0,0 +1,212 @@
+use crate::git;
+use anyhow::Result;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tokio::process::Command;
+
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+const RUSTFLAGS: &str = "RUSTFLAGS";
+const CARGO_ENCODED_RUSTFLAGS: &str = "CARGO_ENCODED_RUSTFLAGS";
+const ENCODED_SEPARATOR: char = '\x1f';
+const CARGO_CONFIG_FILES: [&str; 2] = [".cargo/config.toml", ".cargo/config"];
+
+/// Where the remap flags go, so cargo uses them along with the flags of a normal build
+#[derive(Debug, PartialEq, Eq)]
+enum RustFlags {
+    /// Appended to `CARGO_ENCODED_RUSTFLAGS`, which cargo prefers over anything else
+    Encoded(String),
+    /// Appended to `RUSTFLAGS`, which cargo prefers over the config
+    Plain(String),
+    /// The `build.rustflags` value of `--config`, merged with the cargo config
+    Config(String),
+}
+
+/// Environment of a build whose artifacts do not depend on when or where it ran
+#[derive(Debug, Clone)]
+pub struct Reproducible {
+    source_date_epoch: u64,
+    remaps: Vec<(PathBuf, String)>,
+}
+
+impl Reproducible {
+    /// Uses the commit time of the current tag as the build date
+    pub fn new(workspace: impl AsRef<Path>, target_dir: Option<&Path>) -> Result<Self> {
+        let workspace = workspace.as_ref();
+        let tag = git::get_current_tag(workspace)?;
+        let source_date_epoch = git::get_commit_time(workspace, &tag)?;
+
+        // rustc applies the last matching remap, so the most specific paths go last
+        let mut remaps = vec![];
+        if let Some(home) = env::var_os("HOME") {
+            remaps.push((PathBuf::from(home), "~".to_owned()));
+        }
+        remaps.push((workspace.to_path_buf(), ".".to_owned()));
+        if let Some(target_dir) = target_dir {
+            remaps.push((target_dir.to_path_buf(), "target".to_owned()));
+        }
+
+        if env::var_os(CARGO_ENCODED_RUSTFLAGS).is_none()
+            && env::var_os(RUSTFLAGS).is_none()
+            && has_target_rustflags(workspace)
+        {
+            log::warn!(
+                "the cargo config sets target rustflags, which cargo prefers over build.rustflags: \
+                 the reproducible build drops the path remaps, add them to the target rustflags"
+            );
+        }
+
+        Ok(Reproducible {
+            source_date_epoch,
+            remaps,
+        })
+    }
+
+    pub fn source_date_epoch(&self) -> u64 {
+        self.source_date_epoch
+    }
+
+    /// Sets the build date and remaps the paths, on top of the rustflags cargo would use
+    pub fn apply(&self, command: &mut Command) {
+        command.env(SOURCE_DATE_EPOCH, self.source_date_epoch.to_string());
+
+        let encoded = env::var(CARGO_ENCODED_RUSTFLAGS).ok();
+        let plain = env::var(RUSTFLAGS).ok();
+        match self.rustflags(encoded.as_deref(), plain.as_deref()) {
+            RustFlags::Encoded(flags) => command.env(CARGO_ENCODED_RUSTFLAGS, flags),
+            RustFlags::Plain(flags) => command.env(RUSTFLAGS, flags),
+            RustFlags::Config(flags) => command
+                .arg("--config")
+                .arg(format!("build.rustflags={}", flags)),
+        };
+    }
+
+    /// Adds the remaps to the flags cargo picks first: the encoded ones, then `RUSTFLAGS`,
+    /// then the config, which `--config` merges with instead of replacing
+    fn rustflags(&self, encoded: Option<&str>, plain: Option<&str>) -> RustFlags {
+        let remaps: Vec<String> = self
+            .remaps
+            .iter()
+            .map(|(from, to)| format!("--remap-path-prefix={}={}", from.display(), to))
+            .collect();
+
+        match (encoded, plain) {
+            (Some(encoded), _) => RustFlags::Encoded(
+                encoded
+                    .split(ENCODED_SEPARATOR)
+                    .filter(|flag| !flag.is_empty())
+                    .map(str::to_owned)
+                    .chain(remaps)
+                    .collect::<Vec<_>>()
+                    .join(&ENCODED_SEPARATOR.to_string()),
+            ),
+            (None, Some(plain)) => RustFlags::Plain(
+                plain
+                    .split_whitespace()
+                    .map(str::to_owned)
+                    .chain(remaps)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            (None, None) => RustFlags::Config(toml::Value::from(remaps).to_string()),
+        }
+    }
+}
+
+/// Whether a cargo config file of the workspace, or of its parents, sets target rustflags
+fn has_target_rustflags(workspace: &Path) -> bool {
+    workspace
+        .ancestors()
+        .flat_map(|dir| CARGO_CONFIG_FILES.map(|file| dir.join(file)))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|content| content.parse::<toml::Table>().ok())
+        .any(|config| {
+            config
+                .get("target")
+                .and_then(toml::Value::as_table)
+                .is_some_and(|targets| {
+                    targets.values().any(|target| {
+                        target
+                            .as_table()
+                            .is_some_and(|target| target.contains_key("rustflags"))
+                    })
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reproducible() -> Reproducible {
+        Reproducible {
+            source_date_epoch: 0,
+            remaps: vec![
+                (PathBuf::from("/home/user"), "~".to_owned()),
+                (PathBuf::from("/home/user/tool"), ".".to_owned()),
+            ],
+        }
+    }
+
+    #[test]
+    fn should_append_the_remaps_to_the_existing_rustflags() {
+        assert_eq!(
+            reproducible().rustflags(None, Some("-C target-cpu=native")),
+            RustFlags::Plain(
+                "-C target-cpu=native --remap-path-prefix=/home/user=~ --remap-path-prefix=/home/user/tool=."
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn should_append_the_remaps_to_the_existing_encoded_rustflags() {
+        assert_eq!(
+            reproducible().rustflags(Some("-C\x1ftarget-cpu=native"), Some("-C opt-level=1")),
+            RustFlags::Encoded(
+                "-C\x1ftarget-cpu=native\x1f--remap-path-prefix=/home/user=~\x1f--remap-path-prefix=/home/user/tool=."
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            reproducible().rustflags(Some(""), None),
+            RustFlags::Encoded(
+                "--remap-path-prefix=/home/user=~\x1f--remap-path-prefix=/home/user/tool=."
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn should_merge_the_remaps_with_the_cargo_config() {
+        assert_eq!(
+            reproducible().rustflags(None, None),
+            RustFlags::Config(
+                r#"["--remap-path-prefix=/home/user=~", "--remap-path-prefix=/home/user/tool=."]"#
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn should_find_the_target_rustflags_of_the_cargo_config() -> Result<()> {
+        let dir = tempdir::TempDir::new("reproducible")?;
+        let workspace = dir.path().join("tool");
+        fs::create_dir_all(workspace.join(".cargo"))?;
+        fs::write(
+            workspace.join(".cargo/config.toml"),
+            "[build]\nrustflags = [\"-C\", \"target-cpu=native\"]\n",
+        )?;
+        assert!(!has_target_rustflags(&workspace));
+
+        fs::create_dir_all(dir.path().join(".cargo"))?;
+        fs::write(
+            dir.path().join(".cargo/config"),
+            "[target.x86_64-unknown-linux-gnu]\nrustflags = [\"-C\", \"linker=clang\"]\n",
+        )?;
+        assert!(has_target_rustflags(&workspace));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use super::{DEFAULT_RUSTC_BIN_NAME, DEFAULT_RUSTUP_BIN_NAME};
+use crate::build::Build;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+use tokio::process::Command;
+
+const TOOLCHAIN_FILE_NAME: &str = "rust-toolchain.toml";
+const LEGACY_TOOLCHAIN_FILE_NAME: &str = "rust-toolchain";
+
+/// Installs the rustup targets needed by the build, using the configured toolchain
+#[derive(Debug, Clone, Default)]
+pub struct Toolchain {
+    channel: Option<String>,
+    offline: bool,
+}
+
+#[derive(Deserialize)]
+struct ToolchainFile {
+    toolchain: ToolchainSection,
+}
+
+#[derive(Deserialize)]
+struct ToolchainSection {
+    channel: Option<String>,
+}
+
+impl Toolchain {
+    /// Uses `build.toolchain` when set, the channel of a toolchain file otherwise
+    pub fn new(build: &Build, dir: impl AsRef<Path>) -> Result<Self> {
+        let channel = match &build.toolchain {
+            Some(toolchain) => Some(toolchain.trim_start_matches('+').to_owned()),
+            None => read_toolchain_file(dir.as_ref())?,
+        };
+
+        if let Some(channel) = &channel {
+            log::info!("using the {} toolchain", channel);
+        }
+
+        Ok(Toolchain {
+            channel,
+            offline: build.offline,
+        })
+    }
+
+    /// The `+toolchain` argument for cargo, if any
+    pub fn cargo_arg(&self) -> Option<String> {
+        self.channel.as_ref().map(|channel| format!("+{}", channel))
+    }
+
+    /// The verbose version of the toolchain compiler
+    pub async fn version(&self) -> Result<String> {
+        let mut command = Command::new(DEFAULT_RUSTC_BIN_NAME);
+        if let Some(toolchain) = self.cargo_arg() {
+            command.arg(toolchain);
+        }
+
+        let output = command
+            .arg("-vV")
+            .output()
+            .await
+            .context("Cannot run rustc")?;
+
+        if !output.status.success() {
+            bail!(anyhow::anyhow!(
+                "cannot read the toolchain version: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    }
+
+    pub async fn installed_targets(&self) -> Result<Vec<String>> {
+        let output = self
+            .rustup(&["target", "list", "--installed"])
+            .output()
+            .await
+            .context("Cannot run rustup")?;
+
+        if !output.status.success() {
+            bail!(anyhow::anyhow!(
+                "cannot list the installed targets: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(parse_target_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Installs the missing targets, failing in offline mode if there are any
+    pub async fn ensure_targets(&self, targets: &[String]) -> Result<()> {
+        if which::which(DEFAULT_RUSTUP_BIN_NAME).is_err() {
+            log::warn!("rustup not found, assuming the targets are already installed");
+            return Ok(());
+        }
+
+        let installed = self.installed_targets().await?;
+        let missing: Vec<&String> = targets
+            .iter()
+            .filter(|target| !installed.contains(target))
+            .collect();
+
+        if missing.is_empty() {
+            log::debug!("all targets are installed");
+            return Ok(());
+        }
+
+        if self.offline {
+            bail!(anyhow::anyhow!(
+                "missing rustup targets in offline mode: {}",
+                missing
+                    .iter()
+                    .map(|target| target.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        for target in missing {
+            log::info!("installing target {}", target);
+            let output = self
+                .rustup(&["target", "add", target])
+                .output()
+                .await
+                .context("Cannot run rustup")?;
+
+            if !output.status.success() {
+                bail!(anyhow::anyhow!(
+                    "cannot install the target {}: {}",
+                    target,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rustup(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(DEFAULT_RUSTUP_BIN_NAME);
+        command.args(args);
+        if let Some(channel) = &self.channel {
+            command.args(["--toolchain", channel]);
+        }
+        command
+    }
+}
+
+fn read_toolchain_file(dir: &Path) -> Result<Option<String>> {
+    let toml_file = dir.join(TOOLCHAIN_FILE_NAME);
+    let legacy_file = dir.join(LEGACY_TOOLCHAIN_FILE_NAME);
+
+    let content = if toml_file.is_file() {
+        std::fs::read_to_string(toml_file)?
+    } else if legacy_file.is_file() {
+        std::fs::read_to_string(legacy_file)?
+    } else {
+        return Ok(None);
+    };
+
+    parse_toolchain_file(&content)
+}
+
+/// Reads the channel from either the toml format or the legacy single line format
+fn parse_toolchain_file(content: &str) -> Result<Option<String>> {
+    let content = content.trim();
+
+    if !content.contains('[') {
+        return Ok(Some(content.to_owned()).filter(|channel| !channel.is_empty()));
+    }
+
+    let file: ToolchainFile =
+        toml::from_str(content).context("Cannot parse the rust toolchain file")?;
+
+    Ok(file.toolchain.channel)
+}
+
+fn parse_target_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split_whitespace().next().unwrap_or(line).to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_the_toolchain_file() -> Result<()> {
+        let content = r#"
+[toolchain]
+channel = "nightly-2024-01-01"
+targets = ["aarch64-apple-darwin"]
+"#;
+
+        assert_eq!(
+            parse_toolchain_file(content)?,
+            Some("nightly-2024-01-01".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_parse_the_legacy_toolchain_file() -> Result<()> {
+        assert_eq!(
+            parse_toolchain_file("stable\n")?,
+            Some("stable".to_string())
+        );
+        assert_eq!(parse_toolchain_file("")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_parse_the_installed_targets() {
+        let output = "aarch64-apple-darwin\nx86_64-unknown-linux-gnu (installed)\n\n";
+
+        assert_eq!(
+            parse_target_list(output),
+            vec!["aarch64-apple-darwin", "x86_64-unknown-linux-gnu"]
+        );
+    }
+
+    #[test]
+    fn should_prefix_the_cargo_toolchain() {
+        let build = Build {
+            toolchain: Some("+nightly".to_string()),
+            ..Default::default()
+        };
+
+        let toolchain = Toolchain::new(&build, ".").unwrap();
+
+        assert_eq!(toolchain.cargo_arg(), Some("+nightly".to_string()));
+    }
+}
@@ -0,0 +1,74 @@
+use super::DEFAULT_CARGO_BIN_NAME;
+use anyhow::{bail, Context, Result};
+use std::{fs, path::Path};
+use tokio::process::Command;
+
+const VENDOR_DIR_NAME: &str = "vendor";
+const LOCK_FILE_NAME: &str = "Cargo.lock";
+
+/// Vendors the dependencies of the crate in `dir` and points cargo at them, so that it
+/// builds offline
+pub async fn vendor(dir: impl AsRef<Path>) -> Result<()> {
+    let dir = dir.as_ref();
+    let mut command = Command::new(DEFAULT_CARGO_BIN_NAME);
+    command.arg("vendor").current_dir(dir);
+    if dir.join(LOCK_FILE_NAME).exists() {
+        command.arg("--locked");
+    }
+
+    log::info!("vendoring the dependencies into {}", dir.display());
+    let output = command
+        .arg(VENDOR_DIR_NAME)
+        .output()
+        .await
+        .context("Cannot run cargo vendor")?;
+    if !output.status.success() {
+        bail!(anyhow::anyhow!(
+            "cargo vendor failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    write_config(dir, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// Appends the source replacement printed by cargo vendor to the cargo config of the crate
+fn write_config(dir: &Path, source_replacement: &str) -> Result<()> {
+    let config_dir = dir.join(".cargo");
+    fs::create_dir_all(&config_dir)?;
+
+    let path = config_dir.join("config.toml");
+    let mut config = match fs::read_to_string(&path) {
+        Ok(existing) if !existing.trim().is_empty() => format!("{}\n\n", existing.trim_end()),
+        _ => String::new(),
+    };
+    config.push_str(source_replacement.trim());
+    config.push('\n');
+
+    fs::write(&path, config).with_context(|| format!("Cannot write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn should_append_the_source_replacement_to_the_cargo_config() -> Result<()> {
+        let dir = TempDir::new("vendor")?;
+        let replacement = "[source.crates-io]\nreplace-with = \"vendored-sources\"\n";
+
+        write_config(dir.path(), replacement)?;
+        let path = dir.path().join(".cargo").join("config.toml");
+        assert_eq!(fs::read_to_string(&path)?, replacement);
+
+        fs::write(&path, "[build]\nrustflags = [\"-Dwarnings\"]\n")?;
+        write_config(dir.path(), replacement)?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            format!("[build]\nrustflags = [\"-Dwarnings\"]\n\n{}", replacement)
+        );
+
+        Ok(())
+    }
+}
@@ -1,32 +1,182 @@
 use crate::github::asset::Asset;
 use anyhow::{Context, Result};
-use sha2::{Digest, Sha256};
-use std::{fs::File, io, path::Path};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
 
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// The hash functions the checksums can be computed with
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+    Sha1,
+}
+
+impl ChecksumAlgorithm {
+    /// The lowercase name, also the extension of the per-asset checksum files
+    pub fn name(&self) -> &str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::Sha1 => "sha1",
+        }
+    }
+}
+
+impl Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+    Sha1(Sha1),
+}
+
+impl Hasher {
+    fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            ChecksumAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+            ChecksumAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Sha1(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> Checksum {
+        let (algorithm, value) = match self {
+            Hasher::Sha256(hasher) => (ChecksumAlgorithm::Sha256, hex::encode(hasher.finalize())),
+            Hasher::Sha512(hasher) => (ChecksumAlgorithm::Sha512, hex::encode(hasher.finalize())),
+            Hasher::Blake3(hasher) => (
+                ChecksumAlgorithm::Blake3,
+                hasher.finalize().to_hex().to_string(),
+            ),
+            Hasher::Sha1(hasher) => (ChecksumAlgorithm::Sha1, hex::encode(hasher.finalize())),
+        };
+
+        Checksum { algorithm, value }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Checksum {
+    algorithm: ChecksumAlgorithm,
     value: String,
 }
 
 impl Checksum {
+    /// The SHA-256 checksum of the file
     pub fn new(asset_path: impl AsRef<Path>) -> Result<Self> {
-        Self::create(asset_path)
+        Self::with_algorithm(asset_path, ChecksumAlgorithm::Sha256)
     }
 
-    pub fn value(&self) -> &str {
-        &self.value
+    pub fn with_algorithm(path: impl AsRef<Path>, algorithm: ChecksumAlgorithm) -> Result<Self> {
+        let mut checksums = Self::all(path, &[algorithm])?;
+        Ok(checksums.remove(0))
     }
 
-    fn create(path: impl AsRef<Path>) -> Result<Self> {
+    /// The checksums of the file with every algorithm, reading it once
+    pub fn all(path: impl AsRef<Path>, algorithms: &[ChecksumAlgorithm]) -> Result<Vec<Self>> {
         let path = path.as_ref();
         let mut file = File::open(path).context("Cannot open file")?;
+        let mut hashers: Vec<Hasher> = algorithms.iter().copied().map(Hasher::new).collect();
+
+        let mut buffer = vec![0; BUFFER_SIZE];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            if read == 0 {
+                break;
+            }
+            for hasher in &mut hashers {
+                hasher.update(&buffer[..read]);
+            }
+        }
+
+        Ok(hashers.into_iter().map(Hasher::finish).collect())
+    }
+
+    /// A checksum computed beforehand
+    pub fn from_value(algorithm: ChecksumAlgorithm, value: impl Into<String>) -> Self {
+        Checksum {
+            algorithm,
+            value: value.into(),
+        }
+    }
+
+    pub fn algorithm(&self) -> ChecksumAlgorithm {
+        self.algorithm
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// A writer hashing everything written through it, so the file needs no second read
+pub struct ChecksumWriter<W> {
+    inner: W,
+    hasher: Hasher,
+}
 
-        let mut hasher = Sha256::new();
-        let _ = io::copy(&mut file, &mut hasher)?;
-        let hash = hasher.finalize();
+impl<W: Write> ChecksumWriter<W> {
+    /// Hashes with SHA-256, the checksum every release asset gets
+    pub fn new(inner: W) -> Self {
+        Self::with_algorithm(inner, ChecksumAlgorithm::Sha256)
+    }
 
-        let encoded = hex::encode(hash);
+    pub fn with_algorithm(inner: W, algorithm: ChecksumAlgorithm) -> Self {
+        ChecksumWriter {
+            inner,
+            hasher: Hasher::new(algorithm),
+        }
+    }
 
-        Ok(Checksum { value: encoded })
+    /// Flushes the inner writer and returns it with the checksum of the written bytes
+    pub fn finish(mut self) -> Result<(W, Checksum)> {
+        self.inner.flush()?;
+        Ok((self.inner, self.hasher.finish()))
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
     }
 }
 
@@ -49,7 +199,7 @@ impl TryFrom<Asset> for Checksum {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
+    use std::fs;
     use tempdir::TempDir;
 
     #[test]
@@ -71,6 +221,60 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn should_hash_while_writing() -> Result<()> {
+        let mut writer = ChecksumWriter::new(Vec::new());
+        writeln!(writer, "Hello, world!")?;
+
+        let (written, checksum) = writer.finish()?;
+
+        assert_eq!(written, b"Hello, world!\n");
+        assert_eq!(
+            checksum.value(),
+            "d9014c4624844aa5bac314773d6b689ad467fa4e1d1a50a1b8a99d5a95f72ff5"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_hash_with_every_algorithm() -> Result<()> {
+        let dir = TempDir::new("checksum")?;
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "Hello, world!\n")?;
+
+        let algorithms = [
+            ChecksumAlgorithm::Sha512,
+            ChecksumAlgorithm::Blake3,
+            ChecksumAlgorithm::Sha1,
+        ];
+        let checksums = Checksum::all(&file_path, &algorithms)?;
+
+        assert_eq!(
+            checksums
+                .iter()
+                .map(Checksum::algorithm)
+                .collect::<Vec<_>>(),
+            algorithms
+        );
+        assert_eq!(
+            checksums[0].value(),
+            "09e1e2a84c92b56c8280f4a1203c7cffd61b162cfe987278d4d6be9afbf38c0e\
+             8934cdadf83751f4e99d111352bffefc958e5a4852c8a7a29c95742ce59288a8"
+        );
+        assert_eq!(
+            checksums[2].value(),
+            "09fac8dbfd27bd9b4d23a00eb648aa751789536d"
+        );
+        assert_eq!(checksums[1].value().len(), 64);
+
+        let mut writer = ChecksumWriter::with_algorithm(Vec::new(), ChecksumAlgorithm::Blake3);
+        writeln!(writer, "Hello, world!")?;
+        let (_, checksum) = writer.finish()?;
+        assert_eq!(checksum, checksums[1]);
+
+        Ok(())
+    }
+
     #[test]
     fn should_return_err_with_nonexistent_file() {
         let result = Checksum::new("nonexistent.txt");
@@ -0,0 +1,32 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Default, Subcommand)]
+pub enum Command {
+    /// Builds the project, creates the github release and publishes the brew formula
+    #[default]
+    Release,
+    /// Builds the project twice and checks that both builds produce the same binaries
+    VerifyReproducible,
+    /// Checks the signature of a downloaded release asset, without any config file
+    Verify(VerifyArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// The downloaded asset
+    pub file: PathBuf,
+    /// The public key of the release, a minisign key or a PEM ed25519 key, inline or in a file
+    #[arg(short = 'p', long)]
+    pub public_key: String,
+    /// The signature, defaulting to the asset path with `.minisig` or `.sig` appended
+    #[arg(short = 'x', long)]
+    pub signature: Option<PathBuf>,
+}
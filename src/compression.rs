@@ -1,89 +1,1142 @@
-use anyhow::{Context, Result};
-use flate2::GzBuilder;
+use crate::{
+    build::{binary::Binary, os::Os},
+    checksum::{Checksum, ChecksumWriter},
+    config::{ArchiveFile, FileEntry, FileOwner},
+};
+use anyhow::{bail, Context, Result};
+use bzip2::{read::BzDecoder, write::BzEncoder};
+use flate2::{read::GzDecoder, GzBuilder};
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io::Write, path::PathBuf};
-use tar::Builder;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::{self, File, Metadata},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tar::{Builder, EntryType, Header, HeaderMode};
+use xz2::{read::XzDecoder, write::XzEncoder};
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+const SECONDS_PER_DAY: u64 = 86_400;
+const DIR_MODE: u32 = 0o755;
+const LINK_MODE: u32 = 0o777;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Compression {
     #[default]
+    #[serde(alias = "tar.gz")]
     TarGz,
+    #[serde(alias = "zip")]
+    Zip,
+    #[serde(alias = "tar.xz")]
+    TarXz,
+    #[serde(alias = "tar.zst")]
+    TarZst,
+    #[serde(alias = "tar.bz2")]
+    TarBz2,
+    /// The bare binary, uploaded without any archive
+    #[serde(alias = "binary")]
+    Binary,
 }
 
 impl Compression {
-    pub fn extension(&self) -> &str {
+    /// The archive extension, `None` for the bare binary
+    pub fn extension(&self) -> Option<&str> {
         match self {
-            Compression::TarGz => "tar.gz",
+            Compression::TarGz => Some("tar.gz"),
+            Compression::Zip => Some("zip"),
+            Compression::TarXz => Some("tar.xz"),
+            Compression::TarZst => Some("tar.zst"),
+            Compression::TarBz2 => Some("tar.bz2"),
+            Compression::Binary => None,
+        }
+    }
+
+    /// The asset file name, the bare binary keeping the executable extension of the os
+    pub fn file_name(&self, name: &str, os: &Os) -> String {
+        match self.extension().or(os.executable_extension()) {
+            Some(extension) => format!("{}.{}", name, extension),
+            None => name.to_owned(),
+        }
+    }
+
+    /// The format itself, or the default archive format for the bare binary
+    pub fn archive(&self) -> Compression {
+        match self {
+            Compression::Binary => Compression::default(),
+            archive => archive.to_owned(),
+        }
+    }
+
+    /// The compression level, checked against the range the format supports
+    pub fn level(&self, level: Option<u32>) -> Result<u32> {
+        let (default, min, max) = match self {
+            Compression::Binary => return Ok(0),
+            Compression::TarGz | Compression::Zip => (6, 0, 9),
+            Compression::TarXz => (6, 0, 9),
+            Compression::TarZst => (3, 1, 22),
+            Compression::TarBz2 => (9, 1, 9),
+        };
+
+        match level {
+            None => Ok(default),
+            Some(level) if (min..=max).contains(&level) => Ok(level),
+            Some(level) => bail!(anyhow::anyhow!(
+                "{:?} compression level must be between {} and {}, found {}",
+                self,
+                min,
+                max,
+                level
+            )),
         }
     }
 }
 
+/// Archives every binary found in `binaries_dir` along with the extra files.
+///
+/// When `mtime` is set the archive is deterministic: every entry gets that mtime, uid/gid 0
+/// and normalized permissions, entries are sorted and the gzip header has no name or date.
+///
+/// The archive streams through the encoder into a file under `output_dir`, and is returned
+/// with its checksum
+#[allow(clippy::too_many_arguments)]
 pub fn compress_file(
-    target_name: &str,
-    target_path: PathBuf,
+    binaries: &[Binary],
+    binaries_dir: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
     compressed_file_name: &str,
-    extra_files: &Option<Vec<String>>,
+    layout: &Layout,
     compression: &Compression,
-) -> Result<PathBuf> {
-    match compression {
-        Compression::TarGz => tar_gz(target_name, target_path, compressed_file_name, extra_files),
+    level: Option<u32>,
+    mtime: Option<u64>,
+) -> Result<(PathBuf, Checksum)> {
+    let Some(extension) = compression.extension() else {
+        bail!(anyhow::anyhow!(
+            "the {:?} format is not an archive",
+            compression
+        ));
+    };
+    let level = compression.level(level)?;
+    let entries = Entries::collect(binaries, binaries_dir.as_ref(), layout, mtime.is_some())?;
+    fs::create_dir_all(&output_dir)?;
+    let path = output_dir
+        .as_ref()
+        .join(format!("{}.{}", compressed_file_name, extension));
+
+    for file in &entries.files {
+        log::debug!(
+            "archiving file: {} - {} at {}.",
+            file.name.display(),
+            path.display(),
+            file.path.display(),
+        );
     }
+
+    let file = BufWriter::new(File::create(&path).context("Cannot create the compressed file")?);
+    let writer = ChecksumWriter::new(file);
+    let writer = match compression {
+        Compression::TarGz => {
+            let mut gz_builder = GzBuilder::new();
+            if mtime.is_none() {
+                gz_builder = gz_builder.filename(compressed_file_name);
+            }
+            let encoder = gz_builder.write(writer, flate2::Compression::new(level));
+            tar(&entries, encoder, mtime)?.finish()?
+        }
+        Compression::TarXz => tar(&entries, XzEncoder::new(writer, level), mtime)?.finish()?,
+        Compression::TarZst => {
+            let encoder = zstd::Encoder::new(writer, i32::try_from(level)?)?;
+            tar(&entries, encoder, mtime)?.finish()?
+        }
+        Compression::TarBz2 => {
+            let encoder = BzEncoder::new(writer, bzip2::Compression::new(level));
+            tar(&entries, encoder, mtime)?.finish()?
+        }
+        Compression::Zip => zip(&entries, writer, level, mtime)?,
+        Compression::Binary => unreachable!("the binary format has no extension"),
+    };
+
+    let (_, checksum) = writer.finish()?;
+
+    Ok((path, checksum))
 }
 
-fn tar_gz(
-    target_name: &str,
-    target_path: PathBuf,
-    compressed_file_name: &str,
-    extra_files: &Option<Vec<String>>,
-) -> Result<PathBuf> {
-    let compressed_file_name_with_extension = format!(
-        "{}.{}",
-        compressed_file_name,
-        Compression::TarGz.extension()
-    );
-    log::debug!(
-        "compressing file: {} - {} at {}.",
-        target_name,
-        compressed_file_name_with_extension,
-        target_path.display(),
+/// The extra files of an archive, and the directory wrapping its whole content
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub files: Option<Vec<ArchiveFile>>,
+    pub directory: Option<String>,
+}
+
+impl Layout {
+    /// Adds the file at the root of the archive
+    pub fn push_file(&mut self, path: impl AsRef<Path>) {
+        self.files
+            .get_or_insert_with(Vec::new)
+            .push(ArchiveFile::Entry(FileEntry {
+                src: glob::Pattern::escape(&path.as_ref().to_string_lossy()),
+                strip_parent: true,
+                ..Default::default()
+            }));
+    }
+}
+
+/// The names an archive is expected to hold, slash separated like in zip archives. They are
+/// worked out from the configuration on their own, never through the entries `compress_file`
+/// collects, so the self test catches what the archiver drops
+#[derive(Debug, Clone, Default)]
+pub struct Contents {
+    pub files: BTreeSet<String>,
+    pub dirs: BTreeSet<String>,
+    pub binaries: Vec<String>,
+    root: PathBuf,
+}
+
+impl Contents {
+    /// The binaries, executable under the wrapping directory
+    pub fn new(binaries: &[Binary], directory: Option<&str>) -> Self {
+        let root = PathBuf::from(directory.unwrap_or_default());
+        let binaries: Vec<String> = binaries
+            .iter()
+            .map(|binary| zip_name(&root.join(binary.archive_name())))
+            .collect();
+
+        Contents {
+            files: binaries.iter().cloned().collect(),
+            dirs: directory
+                .map(|directory| zip_name(Path::new(directory)))
+                .into_iter()
+                .collect(),
+            binaries,
+            root,
+        }
+    }
+
+    /// Expects the file at the root of the archive, whether it exists or not
+    pub fn push_file(&mut self, path: impl AsRef<Path>) {
+        let name = path.as_ref().file_name().unwrap_or_default();
+        self.files.insert(zip_name(&self.root.join(name)));
+    }
+
+    /// Expects every match of the archive file, along with what the matched directories hold
+    pub fn push_matches(&mut self, file: &FileEntry) -> Result<()> {
+        let exclude = file
+            .exclude
+            .iter()
+            .map(|pattern| glob::Pattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Cannot read exclude pattern")?;
+
+        for path in glob::glob(&file.src).context("Cannot read glob pattern")? {
+            let path = path.context("Cannot get path")?;
+            let name = if file.strip_parent {
+                PathBuf::from(path.file_name().unwrap_or_default())
+            } else {
+                relative(&path)
+            };
+            let dst = self.root.join(file.dst.to_owned().unwrap_or_default());
+            self.push_tree(&path, &dst.join(name), &exclude)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_tree(&mut self, path: &Path, name: &Path, exclude: &[glob::Pattern]) -> Result<()> {
+        if exclude.iter().any(|pattern| pattern.matches_path(path)) {
+            return Ok(());
+        }
+
+        let metadata = fs::symlink_metadata(path)
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+        if !metadata.is_dir() {
+            if metadata.is_file() || metadata.is_symlink() {
+                self.files.insert(zip_name(name));
+            }
+            return Ok(());
+        }
+
+        let dir = zip_name(name);
+        if !dir.is_empty() {
+            self.dirs.insert(dir);
+        }
+        for child in
+            fs::read_dir(path).with_context(|| format!("Cannot read {}", path.display()))?
+        {
+            let child = child?;
+            self.push_tree(&child.path(), &name.join(child.file_name()), exclude)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An entry read back from an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedEntry {
+    pub name: String,
+    pub mode: u32,
+    pub dir: bool,
+}
+
+/// Lists the entries of the archive, with their permissions
+pub fn list_entries(
+    path: impl AsRef<Path>,
+    compression: &Compression,
+) -> Result<Vec<ArchivedEntry>> {
+    let path = path.as_ref();
+    let mut entries = vec![];
+
+    if *compression == Compression::Zip {
+        let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+        for index in 0..archive.len() {
+            let file = archive.by_index(index)?;
+            entries.push(ArchivedEntry {
+                name: file.name().trim_end_matches('/').to_owned(),
+                mode: file.unix_mode().unwrap_or_default() & 0o7777,
+                dir: file.is_dir(),
+            });
+        }
+        return Ok(entries);
+    }
+
+    let mut archive = tar::Archive::new(tar_reader(path, compression)?);
+    for entry in archive
+        .entries()
+        .with_context(|| format!("Cannot read {}", path.display()))?
+    {
+        let entry = entry?;
+        entries.push(ArchivedEntry {
+            name: zip_name(&entry.path()?),
+            mode: entry.header().mode()? & 0o7777,
+            dir: entry.header().entry_type().is_dir(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Extracts the archive into the directory
+pub fn extract(
+    path: impl AsRef<Path>,
+    compression: &Compression,
+    dir: impl AsRef<Path>,
+) -> Result<()> {
+    let path = path.as_ref();
+    let dir = dir.as_ref();
+
+    if *compression == Compression::Zip {
+        ZipArchive::new(BufReader::new(File::open(path)?))?
+            .extract(dir)
+            .with_context(|| format!("Cannot extract {}", path.display()))?;
+    } else {
+        tar::Archive::new(tar_reader(path, compression)?)
+            .unpack(dir)
+            .with_context(|| format!("Cannot extract {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Decompresses a tar archive
+fn tar_reader(path: &Path, compression: &Compression) -> Result<Box<dyn Read>> {
+    let file = BufReader::new(
+        File::open(path).with_context(|| format!("Cannot open {}", path.display()))?,
     );
 
-    let mut file = File::open(target_path).context("Cannot open file")?;
-    let mut archive = Builder::new(Vec::new());
+    Ok(match compression {
+        Compression::TarGz => Box::new(GzDecoder::new(file)),
+        Compression::TarXz => Box::new(XzDecoder::new(file)),
+        Compression::TarZst => Box::new(zstd::Decoder::with_buffer(file)?),
+        Compression::TarBz2 => Box::new(BzDecoder::new(file)),
+        Compression::Zip | Compression::Binary => bail!(anyhow::anyhow!(
+            "the {:?} format is not a tar archive",
+            compression
+        )),
+    })
+}
+
+/// A file, symlink or directory of the archive, with its name in the archive
+struct Entry {
+    name: PathBuf,
+    path: PathBuf,
+    mode: Option<u32>,
+    owner: Option<FileOwner>,
+    /// The target of a symlink, archived as a link rather than followed
+    link: Option<PathBuf>,
+}
 
-    archive.append_file(target_name, &mut file)?;
+/// The files and directories to archive
+struct Entries {
+    files: Vec<Entry>,
+    dirs: Vec<Entry>,
+    names: HashSet<PathBuf>,
+}
 
-    if let Some(extra) = extra_files {
-        for path in extra {
-            let glob = glob::glob(path).context("Cannot read glob pattern")?;
+impl Entries {
+    fn collect(
+        binaries: &[Binary],
+        binaries_dir: &Path,
+        layout: &Layout,
+        sorted: bool,
+    ) -> Result<Self> {
+        let mut entries = Entries {
+            files: vec![],
+            dirs: vec![],
+            names: HashSet::new(),
+        };
+        let root = PathBuf::from(layout.directory.as_deref().unwrap_or_default());
+        if !root.as_os_str().is_empty() {
+            entries.names.insert(root.to_owned());
+            entries.dirs.push(Entry {
+                name: root.to_owned(),
+                path: binaries_dir.to_owned(),
+                mode: None,
+                owner: None,
+                link: None,
+            });
+        }
 
-            for entry in glob {
-                let path = entry.context("Cannot get path")?;
+        for binary in binaries {
+            entries.push_file(Entry {
+                name: root.join(binary.archive_name()),
+                path: binaries_dir.join(&binary.name),
+                mode: None,
+                owner: None,
+                link: None,
+            });
+        }
 
-                if path.is_file() {
-                    log::debug!("archiving file: {}", path.display());
-                    archive.append_path(path).context("Cannot archive file")?;
-                } else if path.is_dir() {
-                    log::debug!("archiving dir: {}", path.display());
-                    archive
-                        .append_dir(&path, ".")
-                        .context("Cannot archive directory")?;
-                }
+        for file in layout.files.iter().flatten() {
+            let file = file.entry();
+            let exclude = file
+                .exclude
+                .iter()
+                .map(|pattern| glob::Pattern::new(pattern))
+                .collect::<Result<Vec<_>, _>>()
+                .context("Cannot read exclude pattern")?;
+            let glob = glob::glob(&file.src).context("Cannot read glob pattern")?;
+
+            for path in glob {
+                let path = path.context("Cannot get path")?;
+                let name = match (file.strip_parent, path.file_name()) {
+                    (true, Some(file_name)) => PathBuf::from(file_name),
+                    _ => relative(&path),
+                };
+                let name = match &file.dst {
+                    Some(dst) => root.join(dst).join(name),
+                    None => root.join(name),
+                };
+
+                entries.push_path(&path, name, &file, &exclude)?;
+            }
+        }
+
+        if sorted {
+            entries.files.sort_by(|a, b| a.name.cmp(&b.name));
+            entries.dirs.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        Ok(entries)
+    }
+
+    /// Adds the file or symlink, or the directory along with everything it holds.
+    /// Symlinks are never followed, so a link to a parent directory cannot loop
+    fn push_path(
+        &mut self,
+        path: &Path,
+        name: PathBuf,
+        file: &FileEntry,
+        exclude: &[glob::Pattern],
+    ) -> Result<()> {
+        if exclude.iter().any(|pattern| pattern.matches_path(path)) {
+            log::debug!("excluding {}", path.display());
+            return Ok(());
+        }
+
+        let metadata = fs::symlink_metadata(path)
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+
+        if metadata.is_symlink() {
+            let link = fs::read_link(path)
+                .with_context(|| format!("Cannot read the link {}", path.display()))?;
+            if fs::metadata(path).is_err() {
+                log::warn!("{} links to the missing {}", path.display(), link.display());
+            } else if link.is_absolute() {
+                log::warn!(
+                    "{} links to the absolute path {}, which may not exist once extracted",
+                    path.display(),
+                    link.display()
+                );
             }
+
+            self.push_file(Entry {
+                name,
+                path: path.to_owned(),
+                mode: None,
+                owner: file.owner.to_owned(),
+                link: Some(link),
+            });
+        } else if metadata.is_file() {
+            self.push_file(Entry {
+                name,
+                path: path.to_owned(),
+                mode: file.mode()?,
+                owner: file.owner.to_owned(),
+                link: None,
+            });
+        } else if metadata.is_dir() {
+            if !name.as_os_str().is_empty() && self.names.insert(name.to_owned()) {
+                self.dirs.push(Entry {
+                    name: name.to_owned(),
+                    path: path.to_owned(),
+                    mode: None,
+                    owner: file.owner.to_owned(),
+                    link: None,
+                });
+            }
+
+            let mut children = fs::read_dir(path)
+                .with_context(|| format!("Cannot read {}", path.display()))?
+                .map(|child| child.map(|child| child.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            children.sort();
+
+            for child in children {
+                let child_name = name.join(child.file_name().unwrap_or_default());
+                self.push_path(&child, child_name, file, exclude)?;
+            }
+        } else {
+            log::warn!(
+                "{} is not a file, link or directory, skipping it",
+                path.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Adds the file, unless another one already has its name
+    fn push_file(&mut self, entry: Entry) {
+        if self.names.insert(entry.name.to_owned()) {
+            self.files.push(entry);
+        } else {
+            log::warn!("{} is archived more than once", entry.name.display());
+        }
+    }
+}
+
+/// The path without its root, prefix and `.` or `..` components
+fn relative(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Writes the tar archive into the compression encoder, which is returned to be finished
+fn tar<W: Write>(entries: &Entries, writer: W, mtime: Option<u64>) -> Result<W> {
+    let mut archive = Builder::new(writer);
+
+    for dir in &entries.dirs {
+        log::debug!("archiving dir: {}", dir.name.display());
+        append_entry(&mut archive, dir, mtime).context("Cannot archive directory")?;
+    }
+
+    for file in &entries.files {
+        append_entry(&mut archive, file, mtime).context("Cannot archive file")?;
+    }
+
+    archive.into_inner().context("Cannot write to file")
+}
+
+/// Writes a zip archive with the unix permissions of every entry and an entry per directory
+/// Writes the zip archive as a stream, every entry followed by a data descriptor, so the file
+/// is never seeked back into and can be hashed on the fly
+fn zip<W: Write>(entries: &Entries, file: W, level: u32, mtime: Option<u64>) -> Result<W> {
+    let mut writer = ZipWriter::new_stream(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .compression_level(Some(i64::from(level)));
+
+    if entries
+        .files
+        .iter()
+        .chain(&entries.dirs)
+        .any(|entry| entry.owner.is_some())
+    {
+        log::warn!("zip archives do not keep the file owners");
+    }
+
+    let mut dirs = BTreeSet::new();
+    for entry in entries.files.iter().chain(&entries.dirs) {
+        dirs.extend(parent_dirs(&entry.name));
+    }
+    for dir in &entries.dirs {
+        dirs.insert(zip_name(&dir.name));
+    }
+    dirs.remove("");
+
+    let dir_time = zip_date_time(mtime.unwrap_or_default());
+    for dir in dirs {
+        log::debug!("archiving dir: {}", dir);
+        writer
+            .add_directory(
+                dir,
+                options
+                    .unix_permissions(DIR_MODE)
+                    .last_modified_time(dir_time),
+            )
+            .context("Cannot archive directory")?;
+    }
+
+    for entry in &entries.files {
+        if let Some(link) = &entry.link {
+            let modified = match mtime {
+                Some(mtime) => mtime,
+                None => modified_seconds(&fs::symlink_metadata(&entry.path)?),
+            };
+            writer
+                .add_symlink(
+                    zip_name(&entry.name),
+                    link.to_string_lossy(),
+                    SimpleFileOptions::default()
+                        .unix_permissions(LINK_MODE)
+                        .last_modified_time(zip_date_time(modified)),
+                )
+                .context("Cannot archive link")?;
+            continue;
+        }
+
+        let mut file = File::open(&entry.path).context("Cannot open file")?;
+        let metadata = file.metadata()?;
+        let modified = match mtime {
+            Some(mtime) => mtime,
+            None => modified_seconds(&metadata),
+        };
+
+        writer
+            .start_file(
+                zip_name(&entry.name),
+                options
+                    .unix_permissions(
+                        entry
+                            .mode
+                            .unwrap_or_else(|| file_mode(&metadata, mtime.is_some())),
+                    )
+                    .last_modified_time(zip_date_time(modified)),
+            )
+            .context("Cannot archive file")?;
+        io::copy(&mut file, &mut writer).context("Cannot write to file")?;
+    }
+
+    let mut file = writer.finish()?.into_inner();
+    file.flush()?;
+
+    Ok(file)
+}
+
+/// The slash separated name of a path inside a zip archive, without root or `.` components
+fn zip_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parent_dirs(path: &Path) -> Vec<String> {
+    path.ancestors().skip(1).map(zip_name).collect()
+}
+
+/// The permissions of a file, normalized like tar does in deterministic mode
+#[cfg(unix)]
+fn file_mode(metadata: &Metadata, deterministic: bool) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode() & 0o7777;
+    match (deterministic, mode & 0o111 != 0) {
+        (false, _) => mode,
+        (true, true) => 0o755,
+        (true, false) => 0o644,
+    }
+}
+
+/// The executable bits cannot be read, every file is kept runnable
+#[cfg(not(unix))]
+fn file_mode(_metadata: &Metadata, _deterministic: bool) -> u32 {
+    0o755
+}
+
+fn modified_seconds(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Converts seconds since the unix epoch to a zip date, which cannot be before 1980
+fn zip_date_time(seconds: u64) -> DateTime {
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (seconds / SECONDS_PER_DAY) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = seconds % SECONDS_PER_DAY;
+    DateTime::from_date_and_time(
+        u16::try_from(year).unwrap_or_default(),
+        month as u8,
+        day as u8,
+        (time / 3600) as u8,
+        (time % 3600 / 60) as u8,
+        (time % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+/// Appends the file, symlink or directory, with the mtime when deterministic
+/// and the configured mode and owner
+fn append_entry<W: Write>(
+    archive: &mut Builder<W>,
+    entry: &Entry,
+    mtime: Option<u64>,
+) -> Result<()> {
+    let metadata = match entry.link {
+        Some(_) => fs::symlink_metadata(&entry.path),
+        None => fs::metadata(&entry.path),
+    }
+    .with_context(|| format!("Cannot read {}", entry.path.display()))?;
+
+    let mut header = Header::new_gnu();
+    match mtime {
+        Some(mtime) => {
+            header.set_metadata_in_mode(&metadata, HeaderMode::Deterministic);
+            header.set_mtime(mtime);
+        }
+        None => header.set_metadata(&metadata),
+    }
+    if let Some(mode) = entry.mode {
+        header.set_mode(mode);
+    }
+    if let Some(owner) = &entry.owner {
+        header.set_uid(owner.uid);
+        header.set_gid(owner.gid);
+        header.set_username(owner.user.as_deref().unwrap_or_default())?;
+        header.set_groupname(owner.group.as_deref().unwrap_or_default())?;
+    }
+
+    if let Some(link) = &entry.link {
+        header.set_entry_type(EntryType::Symlink);
+        header.set_mode(LINK_MODE);
+        header.set_size(0);
+        archive.append_link(&mut header, &entry.name, link)?;
+    } else if metadata.is_dir() {
+        header.set_size(0);
+        archive.append_data(&mut header, &entry.name, io::empty())?;
+    } else {
+        let file = File::open(&entry.path).context("Cannot open file")?;
+        archive.append_data(&mut header, &entry.name, file)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn should_create_identical_archives_with_a_fixed_mtime() -> Result<()> {
+        let dir = TempDir::new("compression")?;
+        fs::write(dir.path().join("tool"), "binary")?;
+        let binaries = [Binary::new("tool")];
+
+        let first_name = "first";
+        let second_name = "second";
+
+        let (first, first_checksum) = compress_file(
+            &binaries,
+            dir.path(),
+            dir.path(),
+            first_name,
+            &Layout::default(),
+            &Compression::TarGz,
+            None,
+            Some(1),
+        )?;
+        fs::write(dir.path().join("tool"), "binary")?;
+        let (second, _) = compress_file(
+            &binaries,
+            dir.path(),
+            dir.path(),
+            second_name,
+            &Layout::default(),
+            &Compression::TarGz,
+            None,
+            Some(1),
+        )?;
+
+        assert_eq!(fs::read(&first)?, fs::read(second)?);
+        assert_eq!(first_checksum.value(), Checksum::new(&first)?.value());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_write_the_archive_under_the_output_dir() -> Result<()> {
+        let dir = TempDir::new("compression")?;
+        fs::write(dir.path().join("tool"), "binary")?;
+        let dist_dir = dir.path().join("target").join("dist");
+
+        let (path, _) = compress_file(
+            &[Binary::new("tool")],
+            dir.path(),
+            &dist_dir,
+            "tool-v1.0.0",
+            &Layout::default(),
+            &Compression::TarGz,
+            None,
+            None,
+        )?;
+
+        assert_eq!(path, dist_dir.join("tool-v1.0.0.tar.gz"));
+        assert!(path.is_file());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_keep_the_modes_and_directories_in_zip_archives() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("compression")?;
+        fs::write(dir.path().join("tool.exe"), "binary")?;
+        fs::set_permissions(
+            dir.path().join("tool.exe"),
+            fs::Permissions::from_mode(0o755),
+        )?;
+        fs::create_dir(dir.path().join("docs"))?;
+        fs::write(dir.path().join("docs").join("guide.md"), "guide")?;
+
+        let name = "archive";
+        let layout = Layout {
+            files: Some(vec![ArchiveFile::Glob(
+                dir.path().join("docs/*").display().to_string(),
+            )]),
+            ..Default::default()
+        };
+        let (path, checksum) = compress_file(
+            &[Binary::new("tool.exe")],
+            dir.path(),
+            dir.path(),
+            name,
+            &layout,
+            &Compression::Zip,
+            None,
+            Some(1_700_000_000),
+        )?;
+
+        assert_eq!(checksum.value(), Checksum::new(&path)?.value());
+        let mut archive = zip::ZipArchive::new(File::open(&path)?)?;
+        let tool = archive.by_name("tool.exe")?;
+        assert_eq!(tool.unix_mode().map(|mode| mode & 0o7777), Some(0o755));
+        assert_eq!(
+            tool.last_modified()
+                .map(|time| (time.year(), time.month(), time.day())),
+            Some((2023, 11, 14))
+        );
+        drop(tool);
+
+        let docs = zip_name(&dir.path().join("docs"));
+        assert!(archive.by_name(&format!("{}/", docs))?.is_dir());
+        let guide = archive.by_name(&format!("{}/guide.md", docs))?;
+        assert_eq!(guide.unix_mode().map(|mode| mode & 0o7777), Some(0o644));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_bundle_directories_recursively_under_the_wrapping_directory() -> Result<()> {
+        let dir = TempDir::new("compression")?;
+        fs::write(dir.path().join("tool"), "binary")?;
+        let completions = dir.path().join("completions");
+        fs::create_dir_all(completions.join("zsh"))?;
+        fs::write(completions.join("tool.bash"), "bash")?;
+        fs::write(completions.join("tool.fish"), "fish")?;
+        fs::write(completions.join("zsh").join("_tool"), "zsh")?;
+
+        let layout = Layout {
+            files: Some(vec![ArchiveFile::Entry(FileEntry {
+                src: completions.display().to_string(),
+                dst: Some(PathBuf::from("share")),
+                strip_parent: true,
+                exclude: vec!["*.fish".to_owned()],
+                mode: Some("0600".to_owned()),
+                owner: Some(FileOwner {
+                    uid: 1000,
+                    user: Some("release".to_owned()),
+                    ..Default::default()
+                }),
+            })]),
+            directory: Some("tool-v1.0.0".to_owned()),
+        };
+        let name = "archive";
+        let (path, _) = compress_file(
+            &[Binary::new("tool")],
+            dir.path(),
+            dir.path(),
+            name,
+            &layout,
+            &Compression::TarGz,
+            None,
+            Some(1),
+        )?;
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(File::open(path)?));
+        let mut entries = vec![];
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let header = entry.header();
+            entries.push((
+                entry.path()?.display().to_string(),
+                header.mode()?,
+                header.uid()?,
+                header.username()?.map(str::to_owned),
+            ));
         }
+
+        let release = Some("release".to_owned());
+        let owned = |name: &str, mode| {
+            (
+                format!("tool-v1.0.0/{}", name),
+                mode,
+                1000,
+                release.to_owned(),
+            )
+        };
+        assert_eq!(
+            entries,
+            vec![
+                ("tool-v1.0.0".to_owned(), 0o755, 0, Some(String::new())),
+                owned("share/completions", 0o755),
+                owned("share/completions/zsh", 0o755),
+                owned("share/completions/tool.bash", 0o600),
+                owned("share/completions/zsh/_tool", 0o600),
+                ("tool-v1.0.0/tool".to_owned(), 0o644, 0, Some(String::new())),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_write_every_tar_format() -> Result<()> {
+        let dir = TempDir::new("compression")?;
+        fs::write(dir.path().join("tool"), "binary")?;
+
+        for compression in [
+            Compression::TarGz,
+            Compression::TarXz,
+            Compression::TarZst,
+            Compression::TarBz2,
+        ] {
+            let name = "archive";
+            let (path, checksum) = compress_file(
+                &[Binary::new("tool")],
+                dir.path(),
+                dir.path(),
+                name,
+                &Layout::default(),
+                &compression,
+                Some(1),
+                None,
+            )?;
+            assert!(path
+                .display()
+                .to_string()
+                .ends_with(&compression.file_name("archive", &Os::UnknownLinuxGnu)));
+
+            assert_eq!(checksum.value(), Checksum::new(&path)?.value());
+            let mut archive = tar::Archive::new(tar_reader(&path, &compression)?);
+            let mut entry = archive.entries()?.next().unwrap()?;
+            let mut content = String::new();
+            io::Read::read_to_string(&mut entry, &mut content)?;
+
+            assert_eq!(entry.path()?, Path::new("tool"));
+            assert_eq!(content, "binary");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_list_the_expected_contents_of_every_format() -> Result<()> {
+        let dir = TempDir::new("compression")?;
+        fs::write(dir.path().join("tool"), "binary")?;
+        fs::create_dir(dir.path().join("docs"))?;
+        fs::write(dir.path().join("docs").join("guide.md"), "guide")?;
+        fs::write(dir.path().join("docs").join("draft.md"), "draft")?;
+        let binaries = [Binary::new("tool")];
+        let docs = FileEntry {
+            src: dir.path().join("docs").display().to_string(),
+            dst: Some(PathBuf::from("share")),
+            strip_parent: true,
+            exclude: vec!["**/draft.md".to_owned()],
+            ..Default::default()
+        };
+        let layout = Layout {
+            files: Some(vec![ArchiveFile::Entry(docs.to_owned())]),
+            directory: Some("tool-v1.0.0".to_owned()),
+        };
+        let mut expected = Contents::new(&binaries, layout.directory.as_deref());
+        expected.push_matches(&docs)?;
+        assert_eq!(expected.binaries, vec!["tool-v1.0.0/tool".to_owned()]);
+        assert_eq!(
+            expected.files,
+            BTreeSet::from([
+                "tool-v1.0.0/share/docs/guide.md".to_owned(),
+                "tool-v1.0.0/tool".to_owned()
+            ])
+        );
+
+        for compression in [Compression::Zip, Compression::TarXz] {
+            let name = "archive";
+            let (path, _) = compress_file(
+                &binaries,
+                dir.path(),
+                dir.path(),
+                name,
+                &layout,
+                &compression,
+                None,
+                None,
+            )?;
+            let entries = list_entries(&path, &compression)?;
+
+            let files: BTreeSet<String> = entries
+                .iter()
+                .filter(|entry| !entry.dir)
+                .map(|entry| entry.name.to_owned())
+                .collect();
+            assert_eq!(files, expected.files);
+            assert!(expected
+                .dirs
+                .iter()
+                .all(|dir| entries.iter().any(|entry| entry.dir && entry.name == *dir)));
+
+            let extracted = dir.path().join(compression.extension().unwrap());
+            extract(&path, &compression, &extracted)?;
+            assert_eq!(
+                fs::read_to_string(extracted.join("tool-v1.0.0").join("tool"))?,
+                "binary"
+            );
+        }
+
+        Ok(())
     }
 
-    let compressed_file = File::create(&compressed_file_name_with_extension)
-        .context("Cannot create the compressed file")?;
-    let mut encoder = GzBuilder::new()
-        .filename(target_name)
-        .write(compressed_file, flate2::Compression::Default);
+    #[cfg(unix)]
+    #[test]
+    fn should_archive_symlinks_without_following_them() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("compression")?;
+        fs::write(dir.path().join("tool"), "binary")?;
+        let share = dir.path().join("share");
+        fs::create_dir(&share)?;
+        fs::write(share.join("tool.1"), "manual")?;
+        symlink("tool.1", share.join("tool.man"))?;
+        symlink("..", share.join("up"))?;
+        symlink("missing", share.join("broken"))?;
+
+        let mut layout = Layout::default();
+        layout.push_file(&share);
+        for compression in [Compression::TarGz, Compression::Zip] {
+            let name = "archive";
+            let (path, _) = compress_file(
+                &[Binary::new("tool")],
+                dir.path(),
+                dir.path(),
+                name,
+                &layout,
+                &compression,
+                None,
+                Some(1),
+            )?;
+
+            let files: Vec<String> = list_entries(&path, &compression)?
+                .into_iter()
+                .filter(|entry| !entry.dir)
+                .map(|entry| entry.name)
+                .collect();
+            assert_eq!(files.len(), 5);
+            for name in ["tool.1", "tool.man", "up", "broken"] {
+                assert!(files.contains(&format!("share/{}", name)));
+            }
+
+            let extracted = dir.path().join(compression.extension().unwrap());
+            extract(&path, &compression, &extracted)?;
+            let share = extracted.join("share");
+            assert_eq!(fs::read_link(share.join("tool.man"))?, Path::new("tool.1"));
+            assert_eq!(fs::read_link(share.join("up"))?, Path::new(".."));
+            assert_eq!(fs::read_link(share.join("broken"))?, Path::new("missing"));
+            assert_eq!(fs::read_to_string(share.join("tool.man"))?, "manual");
+        }
 
-    encoder
-        .write_all(&archive.into_inner()?)
-        .context("Cannot write to file")?;
+        Ok(())
+    }
 
-    encoder.try_finish()?;
+    #[test]
+    fn should_name_the_bare_binary_after_the_os() {
+        assert_eq!(
+            Compression::Binary.file_name("tool-v1.0.0-x86_64-windows", &Os::PcWindowsMsvc),
+            "tool-v1.0.0-x86_64-windows.exe"
+        );
+        assert_eq!(
+            Compression::Binary.file_name("tool-v1.0.0-x86_64-linux", &Os::UnknownLinuxGnu),
+            "tool-v1.0.0-x86_64-linux"
+        );
+        assert_eq!(
+            Compression::TarXz.file_name("tool-v1.0.0-x86_64-windows", &Os::PcWindowsMsvc),
+            "tool-v1.0.0-x86_64-windows.tar.xz"
+        );
+        assert!(compress_file(
+            &[Binary::new("tool")],
+            ".",
+            ".",
+            "tool",
+            &Layout::default(),
+            &Compression::Binary,
+            None,
+            None
+        )
+        .is_err());
+    }
 
-    Ok(PathBuf::from(compressed_file_name_with_extension))
+    #[test]
+    fn should_check_the_compression_level() {
+        assert_eq!(Compression::TarZst.level(None).unwrap(), 3);
+        assert_eq!(Compression::TarZst.level(Some(19)).unwrap(), 19);
+        assert!(Compression::TarXz.level(Some(10)).is_err());
+        assert!(Compression::TarBz2.level(Some(0)).is_err());
+    }
 }
@@ -1,6 +1,18 @@
-use crate::{brew::repository::Repository, build::Build, compression::Compression};
-use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use crate::{
+    brew::repository::Repository,
+    build::{os::Os, Build, TargetType},
+    bundle,
+    cargo::metadata::{self, Metadata, Package},
+    checksum::ChecksumAlgorithm,
+    compression::{Compression, Layout},
+    cwd,
+    naming::{self, NameContext, ReleaseContext},
+    signing::SignatureFormat,
+};
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::{Path, PathBuf};
 
 const MAIN_BRANCH_NAME: &str = "main";
 const BREW_DEFAULT_COMMIT_MESSAGE: &str = "update formula";
@@ -9,6 +21,10 @@ const PR_DEFAULT_BASE_BRANCH_NAME: &str = MAIN_BRANCH_NAME;
 const PR_DEFAULT_HEAD_BRANCH_NAME: &str = "bumps-formula-version";
 
 const DEFAULT_CONFIG_FILE_NAME: &str = "rustreleaser.yaml";
+const DEFAULT_NAME_TEMPLATE: &str = "{{ name }}-{{ tag }}-{{ arch }}-{{ os }}";
+const SIGNING_KEY_ENV: &str = "RUSTRELEASER_SIGNING_KEY";
+const SIGNING_PASSWORD_ENV: &str = "RUSTRELEASER_SIGNING_PASSWORD";
+const DEFAULT_CHECKSUMS_TEMPLATE: &str = "{{ name }}-{{ version }}-{{ algorithm }}sums.txt";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
@@ -21,20 +37,81 @@ impl Config {
     pub async fn load() -> Result<Config> {
         let config_string = tokio::fs::read_to_string(DEFAULT_CONFIG_FILE_NAME).await?;
 
-        let config = serde_yaml::from_str::<Config>(&config_string)?;
+        let mut config = serde_yaml::from_str::<Config>(&config_string)?;
+
+        match metadata::metadata().await {
+            Ok(metadata) => config.apply_metadata(&metadata, cwd!()),
+            Err(err) => log::warn!("cannot read the cargo metadata: {:#}", err),
+        }
+
+        config.validate()?;
 
         Ok(config)
     }
+
+    /// Fills the fields not set in the config file with the package metadata
+    fn apply_metadata(&mut self, metadata: &Metadata, dir: impl AsRef<Path>) {
+        self.build.target_dir = Some(metadata.target_directory.to_owned());
+
+        let Some(package) = metadata.package_at(dir.as_ref()) else {
+            log::warn!("cannot find the package in the cargo metadata");
+            return;
+        };
+
+        if self.build.binary.is_empty() {
+            self.build.binary = package.binaries();
+        }
+
+        if let Some(brew) = &mut self.brew {
+            brew.apply_package(package);
+        }
+
+        self.release.apply_package(package);
+
+        if package.license.is_some() && !self.release.archive.ships_license(dir.as_ref()) {
+            log::warn!(
+                "the crate declares a license but no license file is archived, \
+                 add one at the crate root or in `release.archive.files`"
+            );
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.build.binary.is_empty() && self.build.target_type() != TargetType::PreBuilt {
+            bail!(anyhow::anyhow!(
+                "no binary to release, please set `build.binary` or add a [[bin]] target"
+            ));
+        }
+
+        for prebuilt in self.build.prebuilt.iter().flatten() {
+            prebuilt.arch_os()?;
+        }
+
+        if self.brew.as_ref().is_some_and(|brew| brew.name.is_empty()) {
+            bail!(anyhow::anyhow!("no formula name, please set `brew.name`"));
+        }
+
+        if self.release.owner.is_empty() || self.release.repo.is_empty() {
+            bail!(anyhow::anyhow!(
+                "no release repository, please set `release.owner` and `release.repo`"
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BrewConfig {
+    /// Defaults to the package name
+    #[serde(default)]
     pub name: String,
     #[serde(default)]
     pub description: String,
     #[serde(default)]
     pub homepage: String,
-    pub install: String,
+    /// Defaults to `bin.install` for every released binary, renaming the bare binary assets
+    pub install: Option<String>,
     #[serde(default)]
     pub license: String,
     #[serde(default = "BrewConfig::main_branch_name")]
@@ -51,6 +128,13 @@ pub struct BrewConfig {
 }
 
 impl BrewConfig {
+    fn apply_package(&mut self, package: &Package) {
+        fill(&mut self.name, &Some(package.name.to_owned()));
+        fill(&mut self.description, &package.description);
+        fill(&mut self.homepage, &package.homepage);
+        fill(&mut self.license, &package.license);
+    }
+
     fn main_branch_name() -> String {
         MAIN_BRANCH_NAME.to_owned()
     }
@@ -90,9 +174,13 @@ impl PullRequestConfig {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReleaseConfig {
+    /// Defaults to the owner of the package repository, when hosted on github
+    #[serde(default)]
     pub owner: String,
+    /// Defaults to the name of the package repository, when hosted on github
+    #[serde(default)]
     pub repo: String,
     #[serde(default = "ReleaseConfig::target_branch")]
     pub target_branch: String,
@@ -105,17 +193,672 @@ pub struct ReleaseConfig {
     pub body: String,
     #[serde(default)]
     pub archive: Archive,
+    /// Fails the release when the artifacts grow beyond it
+    pub size_budget: Option<SizeBudget>,
+    /// Uploads an archive of the per-target build logs
+    #[serde(default)]
+    pub upload_build_logs: bool,
+    /// Uploads `name-version-src.tar.gz`, the source tree of the tag
+    #[serde(default)]
+    pub upload_source: bool,
+    /// Uploads `name-version-vendored.tar.gz`, the source tree along with the `cargo vendor`
+    /// output and the cargo config using it
+    #[serde(default)]
+    pub upload_vendored_source: bool,
+    #[serde(default)]
+    pub checksum: ChecksumConfig,
+    /// Signs the assets and the combined checksums files
+    pub signing: Option<SigningConfig>,
 }
 
 impl ReleaseConfig {
+    fn apply_package(&mut self, package: &Package) {
+        if let Some((owner, repo)) = package.github_repository() {
+            fill(&mut self.owner, &Some(owner));
+            fill(&mut self.repo, &Some(repo));
+        }
+    }
+
     pub fn target_branch() -> String {
         MAIN_BRANCH_NAME.to_owned()
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Archive {
     #[serde(default)]
     pub compression: Compression,
-    pub files: Option<Vec<String>>,
+    /// Formats replacing `compression` on some os, merged with the default zip on windows
+    /// unless they override windows too
+    #[serde(
+        default = "Archive::default_overrides",
+        deserialize_with = "Archive::merge_overrides"
+    )]
+    pub overrides: Vec<FormatOverride>,
+    /// The compression level, defaulting to the format default
+    pub level: Option<u32>,
+    pub files: Option<Vec<ArchiveFile>>,
+    /// Bundles the LICENSE, README and CHANGELOG files of the crate root along with `files`
+    #[serde(default = "Archive::bundle_docs")]
+    pub bundle_docs: bool,
+    /// Bundles the licenses of the dependencies of each target as `THIRD_PARTY_LICENSES`
+    pub third_party_licenses: Option<LicensesConfig>,
+    /// The name of the binary assets without extension, rendered with handlebars from
+    /// `name`, `binary` (the name of the first binary in the archive), `version`, `tag`, `os`,
+    /// `arch`, `target` and `format`
+    pub name_template: Option<String>,
+    /// Puts the content of the archives under a top-level directory, named after the asset
+    /// unless templated.
+    /// Homebrew steps into that single directory when staging, so the install block is unchanged
+    pub wrap_in_directory: Option<WrapInDirectory>,
+    /// Runs a command from the extracted archives of the targets the host can run
+    pub smoke_test: Option<SmokeTest>,
+}
+
+impl Archive {
+    /// The archive format of the os
+    pub fn compression(&self, os: &Os) -> &Compression {
+        self.overrides
+            .iter()
+            .find(|format| format.os == *os)
+            .map(|format| &format.compression)
+            .unwrap_or(&self.compression)
+    }
+
+    /// The compression level of the os archive format, the global one unless its override sets one
+    pub fn level(&self, os: &Os) -> Option<u32> {
+        self.overrides
+            .iter()
+            .find(|format| format.os == *os)
+            .and_then(|format| format.level)
+            .or(self.level)
+    }
+
+    /// The name of the binary asset, without extension
+    pub fn asset_name(&self, context: &NameContext) -> Result<String> {
+        context.render(
+            self.name_template
+                .as_deref()
+                .unwrap_or(DEFAULT_NAME_TEMPLATE),
+        )
+    }
+
+    /// The extra files and the wrapping directory of the binaries archive
+    pub fn layout(&self, context: &NameContext) -> Result<Layout> {
+        let directory = match &self.wrap_in_directory {
+            None | Some(WrapInDirectory::Enabled(false)) => None,
+            Some(WrapInDirectory::Enabled(true)) => Some(self.asset_name(context)?),
+            Some(WrapInDirectory::Template(template)) => Some(context.render(template)?),
+        };
+
+        Ok(Layout {
+            files: self.files_at(cwd!())?,
+            directory,
+        })
+    }
+
+    /// The configured files, and the doc files of the crate root they leave out
+    fn files_at(&self, dir: impl AsRef<Path>) -> Result<Option<Vec<ArchiveFile>>> {
+        let docs = self
+            .bundled_docs(dir)?
+            .into_iter()
+            .map(|doc| ArchiveFile::Glob(glob::Pattern::escape(&doc.to_string_lossy())));
+
+        let files: Vec<ArchiveFile> = self.files.iter().flatten().cloned().chain(docs).collect();
+        Ok((!files.is_empty()).then_some(files))
+    }
+
+    /// The doc files of `dir` bundled at the root of the archives, those the configured files
+    /// leave out
+    pub fn bundled_docs(&self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        if !self.bundle_docs {
+            return Ok(vec![]);
+        }
+
+        let patterns = self
+            .files
+            .iter()
+            .flatten()
+            .filter_map(|file| glob::Pattern::new(&file.entry().src).ok())
+            .collect::<Vec<_>>();
+
+        Ok(bundle::doc_files(dir)?
+            .into_iter()
+            .filter(|doc| !patterns.iter().any(|pattern| pattern.matches_path(doc)))
+            .collect())
+    }
+
+    /// Whether a license file of the crate in `dir` ends up in the archives
+    pub fn ships_license(&self, dir: impl AsRef<Path>) -> bool {
+        let dir = dir.as_ref();
+        let configured = self.files.iter().flatten().any(|file| {
+            let pattern = dir.join(file.entry().src);
+            glob::glob(&pattern.to_string_lossy())
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(|path| bundle::is_license(&path))
+        });
+        let bundled = self.bundle_docs
+            && bundle::doc_files(dir)
+                .unwrap_or_default()
+                .iter()
+                .any(|doc| bundle::is_license(doc));
+
+        configured || bundled
+    }
+
+    fn bundle_docs() -> bool {
+        true
+    }
+
+    fn default_overrides() -> Vec<FormatOverride> {
+        vec![FormatOverride {
+            os: Os::PcWindowsMsvc,
+            compression: Compression::Zip,
+            level: None,
+        }]
+    }
+
+    /// The configured overrides, along with the defaults of the os they leave out
+    fn merge_overrides<'de, D>(deserializer: D) -> Result<Vec<FormatOverride>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut overrides = Vec::<FormatOverride>::deserialize(deserializer)?;
+        let defaults: Vec<FormatOverride> = Archive::default_overrides()
+            .into_iter()
+            .filter(|default| !overrides.iter().any(|format| format.os == default.os))
+            .collect();
+        overrides.extend(defaults);
+
+        Ok(overrides)
+    }
+}
+
+impl Default for Archive {
+    fn default() -> Self {
+        Archive {
+            compression: Compression::default(),
+            overrides: Archive::default_overrides(),
+            level: None,
+            files: None,
+            bundle_docs: Archive::bundle_docs(),
+            third_party_licenses: None,
+            name_template: None,
+            wrap_in_directory: None,
+            smoke_test: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatOverride {
+    pub os: Os,
+    pub compression: Compression,
+    pub level: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LicensesConfig {
+    /// The licenses the dependencies may use, any license being accepted when unset
+    pub allow: Option<Vec<String>>,
+    /// A handlebars template replacing the default one
+    pub template: Option<PathBuf>,
+}
+
+/// How the checksums of the assets are uploaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChecksumConfig {
+    pub mode: ChecksumMode,
+    /// The hash functions, each one getting its own checksum files, SHA-256 when empty.
+    /// The brew formula keeps SHA-256, the only hash Homebrew accepts
+    pub algorithms: Vec<ChecksumAlgorithm>,
+    /// The name of the combined files, rendered with handlebars from `name`, `version`, `tag`
+    /// and `algorithm`
+    pub name_template: Option<String>,
+}
+
+impl Default for ChecksumConfig {
+    fn default() -> Self {
+        ChecksumConfig {
+            mode: ChecksumMode::default(),
+            algorithms: vec![ChecksumAlgorithm::default()],
+            name_template: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChecksumsFileContext<'a> {
+    #[serde(flatten)]
+    release: &'a ReleaseContext,
+    algorithm: &'a str,
+}
+
+impl ChecksumConfig {
+    /// The distinct hash functions, falling back to SHA-256 rather than uploading no checksum
+    pub fn algorithms(&self) -> Vec<ChecksumAlgorithm> {
+        if self.algorithms.is_empty() {
+            log::warn!(
+                "no checksum algorithm configured, falling back to {}",
+                ChecksumAlgorithm::default()
+            );
+            return vec![ChecksumAlgorithm::default()];
+        }
+
+        self.algorithms.iter().copied().unique().collect()
+    }
+
+    /// The name of the file listing the checksums of every asset with the algorithm
+    pub fn file_name(
+        &self,
+        context: &ReleaseContext,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<String> {
+        naming::render(
+            self.name_template
+                .as_deref()
+                .unwrap_or(DEFAULT_CHECKSUMS_TEMPLATE),
+            &ChecksumsFileContext {
+                release: context,
+                algorithm: algorithm.name(),
+            },
+        )
+    }
+}
+
+/// A checksum file per asset, a single file listing every asset, or both
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChecksumMode {
+    #[default]
+    PerAsset,
+    Combined,
+    Both,
+}
+
+impl ChecksumMode {
+    pub fn per_asset(&self) -> bool {
+        matches!(self, ChecksumMode::PerAsset | ChecksumMode::Both)
+    }
+
+    pub fn combined(&self) -> bool {
+        matches!(self, ChecksumMode::Combined | ChecksumMode::Both)
+    }
+}
+
+/// The key signing the release assets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SigningConfig {
+    pub format: SignatureFormat,
+    /// The secret key file, a minisign key or a PKCS#8 PEM ed25519 key
+    pub key_file: Option<PathBuf>,
+    /// The environment variable holding the secret key when no key file is set
+    pub key_env: String,
+    /// The environment variable holding the password of an encrypted minisign key
+    pub password_env: String,
+}
+
+impl Default for SigningConfig {
+    fn default() -> Self {
+        SigningConfig {
+            format: SignatureFormat::default(),
+            key_file: None,
+            key_env: SIGNING_KEY_ENV.to_owned(),
+            password_env: SIGNING_PASSWORD_ENV.to_owned(),
+        }
+    }
+}
+
+/// A command checking that the archived binary runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmokeTest {
+    /// Rendered like `name_template`, the first word being the binary in the archive
+    pub command: String,
+    /// The text the output must contain, rendered like `command`
+    pub expect: String,
+}
+
+impl Default for SmokeTest {
+    fn default() -> Self {
+        SmokeTest {
+            command: "{{ binary }} --version".to_owned(),
+            expect: "{{ version }}".to_owned(),
+        }
+    }
+}
+
+/// Either enables the default directory, or gives its template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WrapInDirectory {
+    Enabled(bool),
+    Template(String),
+}
+
+/// A file bundled in the archives: a glob, or an entry mapping its matches into the archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ArchiveFile {
+    Glob(String),
+    Entry(FileEntry),
+}
+
+impl ArchiveFile {
+    /// The entry of the file, a glob keeping the source paths of its matches
+    pub fn entry(&self) -> FileEntry {
+        match self {
+            ArchiveFile::Glob(src) => FileEntry {
+                src: src.to_owned(),
+                ..Default::default()
+            },
+            ArchiveFile::Entry(entry) => entry.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Glob of the files or directories to bundle, directories being added recursively
+    pub src: String,
+    /// Directory of the matches inside the archive, defaults to the archive root
+    pub dst: Option<PathBuf>,
+    /// Keeps only the name of the matches, without their parent directories
+    #[serde(default)]
+    pub strip_parent: bool,
+    /// Globs of the files and directories left out
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Permissions of the files, in octal such as `0644`
+    pub mode: Option<String>,
+    pub owner: Option<FileOwner>,
+}
+
+impl FileEntry {
+    pub fn mode(&self) -> Result<Option<u32>> {
+        self.mode
+            .as_ref()
+            .map(|mode| {
+                u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                    .map_err(|_| anyhow::anyhow!("invalid file mode {}", mode))
+            })
+            .transpose()
+    }
+}
+
+/// The owner of archived files, only kept by tar archives
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileOwner {
+    pub uid: u64,
+    pub gid: u64,
+    pub user: Option<String>,
+    pub group: Option<String>,
+}
+
+/// Limits on the artifacts size, in bytes or in growth since the previous release
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct SizeBudget {
+    pub max_binary_size: Option<u64>,
+    pub max_archive_size: Option<u64>,
+    pub max_growth_percent: Option<f64>,
+}
+
+fn fill(field: &mut String, value: &Option<String>) {
+    if let (true, Some(value)) = (field.is_empty(), value) {
+        value.clone_into(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build::arch::Arch, cargo::arch_os_matrix::ArchOsMatrixEntry, git::tag::Tag};
+
+    #[test]
+    fn should_reject_prebuilt_binaries_without_a_target() {
+        let yaml = r#"
+build:
+  prebuilt:
+    - path: dist/tool
+      arch: amd64
+release:
+  owner: rvigo
+  repo: tool
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("dist/tool needs both an arch and an os"));
+    }
+
+    #[test]
+    fn should_use_zip_on_windows_by_default() {
+        let archive: Archive = serde_yaml::from_str("compression: TarGz").unwrap();
+
+        assert_eq!(archive.compression(&Os::PcWindowsMsvc), &Compression::Zip);
+        assert_eq!(
+            archive.compression(&Os::UnknownLinuxGnu),
+            &Compression::TarGz
+        );
+    }
+
+    #[test]
+    fn should_override_the_format_per_os() {
+        let yaml = r#"
+compression: zip
+level: 9
+overrides:
+  - os: linux
+    compression: tar.xz
+"#;
+        let archive: Archive = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(archive.compression(&Os::AppleDarwin), &Compression::Zip);
+        assert_eq!(archive.level(&Os::AppleDarwin), Some(9));
+        assert_eq!(
+            archive.compression(&Os::UnknownLinuxGnu),
+            &Compression::TarXz
+        );
+        assert_eq!(archive.level(&Os::UnknownLinuxGnu), Some(9));
+        assert_eq!(archive.compression(&Os::PcWindowsMsvc), &Compression::Zip);
+    }
+
+    #[test]
+    fn should_fall_back_to_the_global_level_without_an_override_level() {
+        let yaml = r#"
+level: 9
+overrides:
+  - os: linux
+    compression: tar.xz
+  - os: darwin
+    compression: tar.zst
+    level: 19
+"#;
+        let archive: Archive = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(archive.level(&Os::UnknownLinuxGnu), Some(9));
+        assert_eq!(archive.level(&Os::PcWindowsMsvc), Some(9));
+        assert_eq!(archive.level(&Os::AppleDarwin), Some(19));
+    }
+
+    #[test]
+    fn should_keep_the_default_overrides_of_the_os_left_out() {
+        let yaml = r#"
+overrides:
+  - os: linux-musl
+    compression: tar.xz
+"#;
+        let archive: Archive = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(
+            archive.compression(&Os::UnknownLinuxMusl),
+            &Compression::TarXz
+        );
+        assert_eq!(archive.compression(&Os::PcWindowsMsvc), &Compression::Zip);
+        assert_eq!(
+            archive.compression(&Os::UnknownLinuxGnu),
+            &Compression::TarGz
+        );
+
+        let yaml = r#"
+overrides:
+  - os: windows
+    compression: tar.gz
+"#;
+        let archive: Archive = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(archive.overrides.len(), 1);
+        assert_eq!(archive.compression(&Os::PcWindowsMsvc), &Compression::TarGz);
+    }
+
+    fn context() -> NameContext {
+        NameContext::for_target(
+            "tool",
+            &Tag::new("v1.0.0"),
+            &ArchOsMatrixEntry::new(&Arch::Amd64, &Os::UnknownLinuxGnu),
+            &Compression::TarGz,
+        )
+    }
+
+    #[test]
+    fn should_name_assets_after_the_template() {
+        let archive = Archive::default();
+        assert_eq!(
+            archive.asset_name(&context()).unwrap(),
+            "tool-v1.0.0-x86_64-linux"
+        );
+
+        let archive: Archive =
+            serde_yaml::from_str("name_template: '{{ name }}_{{ version }}_{{ target }}'").unwrap();
+        assert_eq!(
+            archive.asset_name(&context()).unwrap(),
+            "tool_1.0.0_x86_64-unknown-linux-gnu"
+        );
+    }
+
+    #[test]
+    fn should_wrap_in_the_default_or_templated_directory() {
+        let context = context();
+        let layout = |yaml: &str| {
+            let archive: Archive = serde_yaml::from_str(yaml).unwrap();
+            archive.layout(&context).unwrap().directory
+        };
+
+        assert_eq!(layout("level: 9"), None);
+        assert_eq!(layout("wrap_in_directory: false"), None);
+        assert_eq!(
+            layout("wrap_in_directory: true").as_deref(),
+            Some("tool-v1.0.0-x86_64-linux")
+        );
+        assert_eq!(
+            layout("wrap_in_directory: '{{ name }}-{{ tag }}'").as_deref(),
+            Some("tool-v1.0.0")
+        );
+    }
+
+    #[test]
+    fn should_name_the_combined_checksums_file() {
+        let context = ReleaseContext::new("tool", &Tag::new("v1.0.0"));
+
+        let checksum = ChecksumConfig::default();
+        assert_eq!(checksum.mode, ChecksumMode::PerAsset);
+        assert_eq!(checksum.algorithms, vec![ChecksumAlgorithm::Sha256]);
+        assert_eq!(
+            checksum
+                .file_name(&context, ChecksumAlgorithm::Sha256)
+                .unwrap(),
+            "tool-1.0.0-sha256sums.txt"
+        );
+
+        let yaml = "mode: both\nalgorithms: [sha512, blake3]\nname_template: SHA256SUMS";
+        let checksum: ChecksumConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(checksum.mode.per_asset() && checksum.mode.combined());
+        assert_eq!(
+            checksum.algorithms,
+            vec![ChecksumAlgorithm::Sha512, ChecksumAlgorithm::Blake3]
+        );
+        assert_eq!(
+            checksum
+                .file_name(&context, ChecksumAlgorithm::Blake3)
+                .unwrap(),
+            "SHA256SUMS"
+        );
+
+        let checksum: ChecksumConfig = serde_yaml::from_str("algorithms: [sha1, sha1]").unwrap();
+        assert_eq!(checksum.algorithms(), vec![ChecksumAlgorithm::Sha1]);
+        let checksum: ChecksumConfig = serde_yaml::from_str("algorithms: []").unwrap();
+        assert_eq!(checksum.algorithms(), vec![ChecksumAlgorithm::Sha256]);
+
+        let checksum: ChecksumConfig =
+            serde_yaml::from_str("name_template: '{{ name }}.{{ algorithm }}'").unwrap();
+        assert_eq!(
+            checksum
+                .file_name(&context, ChecksumAlgorithm::Sha1)
+                .unwrap(),
+            "tool.sha1"
+        );
+    }
+
+    #[test]
+    fn should_bundle_the_doc_files_not_listed() {
+        let dir = tempdir::TempDir::new("config").unwrap();
+        for name in ["LICENSE", "README.md", "CHANGELOG.md"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+        let sources = |archive: &Archive| {
+            archive
+                .files_at(dir.path())
+                .unwrap()
+                .unwrap_or_default()
+                .iter()
+                .map(|file| file.entry().src)
+                .collect::<Vec<_>>()
+        };
+
+        let archive: Archive = serde_yaml::from_str("files: [README*]").unwrap();
+        assert_eq!(
+            sources(&archive),
+            vec!["README*", "CHANGELOG.md", "LICENSE"]
+        );
+        assert!(archive.ships_license(dir.path()));
+
+        let archive: Archive =
+            serde_yaml::from_str("files: [README*]\nbundle_docs: false").unwrap();
+        assert_eq!(sources(&archive), vec!["README*"]);
+        assert!(!archive.ships_license(dir.path()));
+    }
+
+    #[test]
+    fn should_read_globs_and_file_entries() {
+        let yaml = r#"
+files:
+  - LICENSE
+  - src: completions/*
+    dst: share
+    strip_parent: true
+    exclude: ["*.fish"]
+    mode: "0644"
+    owner:
+      user: root
+"#;
+        let archive: Archive = serde_yaml::from_str(yaml).unwrap();
+        let files = archive.files.unwrap();
+
+        let license = files[0].entry();
+        assert_eq!(license.src, "LICENSE");
+        assert_eq!(license.dst, None);
+        assert_eq!(license.mode().unwrap(), None);
+
+        let completions = files[1].entry();
+        assert_eq!(completions.dst, Some(PathBuf::from("share")));
+        assert!(completions.strip_parent);
+        assert_eq!(completions.exclude, vec!["*.fish"]);
+        assert_eq!(completions.mode().unwrap(), Some(0o644));
+        assert_eq!(completions.owner.unwrap().user.as_deref(), Some("root"));
+    }
 }
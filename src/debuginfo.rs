@@ -0,0 +1,230 @@
+use crate::{
+    build::{binary::Binary, os::Os, strip::Strip, Build},
+    cargo,
+};
+use anyhow::{bail, Context, Result};
+use std::{fs, path::Path};
+use tokio::process::Command;
+
+const DEFAULT_DSYMUTIL_BIN_NAME: &str = "dsymutil";
+const ELF_DEBUG_EXTENSION: &str = "debug";
+const MACHO_DEBUG_EXTENSION: &str = "dwarf";
+
+/// Copies every built binary into the dist dir and strips the copy, splitting the debug info
+/// into a file next to it when configured. The cargo output is left untouched, so it is never
+/// stripped twice nor taken as fresh by cargo
+pub async fn strip(build: &Build) -> Result<()> {
+    let Some(strip) = &build.strip else {
+        return Ok(());
+    };
+
+    for target in cargo::targets(build) {
+        let os = target
+            .as_ref()
+            .map(|entry| entry.os.to_owned())
+            .unwrap_or_else(Os::host);
+        let target = target.map(|entry| entry.to_string());
+        let release_dir = build.release_dir(target.as_deref());
+        let dir = build.binaries_dir(target.as_deref());
+        fs::create_dir_all(&dir)?;
+
+        let binaries = build.binaries(&os);
+        for binary in &binaries {
+            fs::copy(release_dir.join(&binary.name), dir.join(&binary.name))
+                .with_context(|| format!("Cannot copy {}", binary.name))?;
+        }
+
+        if !is_supported(&os) {
+            log::info!(
+                "not stripping {}, windows keeps the debug info in pdb files",
+                target.as_deref().unwrap_or("host")
+            );
+            continue;
+        }
+
+        for binary in &binaries {
+            log::info!(
+                "stripping {} ({})",
+                binary.name,
+                target.as_deref().unwrap_or("host")
+            );
+            strip_binary(strip, &dir, binary, &os)
+                .await
+                .with_context(|| format!("Cannot strip {}", binary.name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the binaries of the os can be stripped with objcopy or strip
+pub fn is_supported(os: &Os) -> bool {
+    *os != Os::PcWindowsMsvc
+}
+
+/// The names of the debug info files produced for the binaries, after their name inside the
+/// archive so the debug link still matches once extracted
+pub fn debug_files(binaries: &[Binary], os: &Os) -> Vec<String> {
+    binaries
+        .iter()
+        .map(|binary| debug_file_name(binary.archive_name(), os))
+        .collect()
+}
+
+fn debug_file_name(name: &str, os: &Os) -> String {
+    match os {
+        Os::AppleDarwin => format!("{}.{}", name, MACHO_DEBUG_EXTENSION),
+        _ => format!("{}.{}", name, ELF_DEBUG_EXTENSION),
+    }
+}
+
+async fn strip_binary(strip: &Strip, dir: &Path, binary: &Binary, os: &Os) -> Result<()> {
+    let name = binary.name.as_str();
+    let debug_file = debug_file_name(binary.archive_name(), os);
+
+    if strip.split_debug_info {
+        match os {
+            Os::AppleDarwin => {
+                run(
+                    dir,
+                    DEFAULT_DSYMUTIL_BIN_NAME,
+                    &["--flat", name, "-o", &debug_file],
+                )
+                .await?
+            }
+            _ => {
+                run(
+                    dir,
+                    &strip.objcopy,
+                    &["--only-keep-debug", name, &debug_file],
+                )
+                .await?
+            }
+        }
+    }
+
+    let (program, args) = strip_command(strip, name, os);
+    run(dir, program, &args).await?;
+
+    if strip.split_debug_info && *os != Os::AppleDarwin {
+        let debuglink = format!("--add-gnu-debuglink={}", debug_file);
+        run(dir, &strip.objcopy, &[&debuglink, name]).await?;
+    }
+
+    Ok(())
+}
+
+/// The command removing the debug info and the symbol table: GNU objcopy cannot rewrite
+/// Mach-O files, so darwin binaries go through strip, keeping only the exported symbols
+fn strip_command<'a>(strip: &'a Strip, name: &'a str, os: &Os) -> (&'a str, Vec<&'a str>) {
+    match os {
+        Os::AppleDarwin => (&strip.strip, vec!["-S", "-x", name]),
+        _ => (&strip.objcopy, vec!["--strip-all", name]),
+    }
+}
+
+async fn run(dir: &Path, program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .with_context(|| format!("Cannot run {}", program))?;
+
+    if !output.status.success() {
+        bail!(anyhow::anyhow!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_name_the_debug_files_after_the_binaries() {
+        let binaries = vec![
+            Binary::new("tool"),
+            Binary {
+                name: "tool-daemon".to_string(),
+                rename: Some("toold".to_string()),
+            },
+        ];
+
+        assert_eq!(
+            debug_files(&binaries, &Os::UnknownLinuxGnu),
+            vec!["tool.debug", "toold.debug"]
+        );
+        assert_eq!(
+            debug_files(&binaries, &Os::AppleDarwin),
+            vec!["tool.dwarf", "toold.dwarf"]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn should_strip_copies_and_run_twice_on_the_same_tree() -> Result<()> {
+        let dir = tempdir::TempDir::new("debuginfo")?;
+        let build = Build {
+            binary: vec![Binary::new("tool")],
+            target_dir: Some(dir.path().join("target")),
+            dist: Some(dir.path().join("dist")),
+            strip: Some(Strip {
+                split_debug_info: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let release_dir = build.release_dir(None);
+        fs::create_dir_all(&release_dir)?;
+        // a system binary without its own debug link, standing in for the cargo output
+        run(
+            &release_dir,
+            "objcopy",
+            &[
+                "--remove-section=.gnu_debuglink",
+                "--remove-section=.gnu_debugaltlink",
+                "/bin/true",
+                "tool",
+            ],
+        )
+        .await?;
+        let built = fs::read(release_dir.join("tool"))?;
+
+        strip(&build).await?;
+        let debug_info = fs::read(build.binaries_dir(None).join("tool.debug"))?;
+        strip(&build).await?;
+
+        assert_eq!(fs::read(release_dir.join("tool"))?, built);
+        assert_eq!(
+            fs::read(build.binaries_dir(None).join("tool.debug"))?,
+            debug_info
+        );
+        assert!(build.binaries_dir(None).join("tool").is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_strip_darwin_binaries_with_strip() {
+        let strip = Strip {
+            strip: "llvm-strip".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            strip_command(&strip, "tool", &Os::UnknownLinuxGnu),
+            ("objcopy", vec!["--strip-all", "tool"])
+        );
+        assert_eq!(
+            strip_command(&strip, "tool", &Os::AppleDarwin),
+            ("llvm-strip", vec!["-S", "-x", "tool"])
+        );
+    }
+}
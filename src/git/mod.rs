@@ -2,11 +2,17 @@ pub mod committer;
 pub mod tag;
 
 use anyhow::{bail, Context, Result};
-use git2::Repository;
+use git2::{ObjectType, Repository, Status, StatusOptions, TreeWalkMode, TreeWalkResult};
 use semver::Version;
-use std::path::Path;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use tag::Tag;
 
+const FILEMODE_EXECUTABLE: i32 = 0o100755;
+const FILEMODE_LINK: i32 = 0o120000;
+
 pub fn get_current_tag(repo_path: impl AsRef<Path>) -> Result<Tag> {
     let repo = Repository::open(repo_path).context("Cannot read repo info")?;
     let tags = repo.tag_names(None)?;
@@ -31,6 +37,111 @@ pub fn get_current_tag(repo_path: impl AsRef<Path>) -> Result<Tag> {
     Ok(tag.to_owned())
 }
 
+/// The commit time of the tagged commit, in seconds since the unix epoch
+pub fn get_commit_time(repo_path: impl AsRef<Path>, tag: &Tag) -> Result<u64> {
+    let repo = Repository::open(repo_path).context("Cannot read repo info")?;
+    let commit = repo
+        .revparse_single(tag.name())
+        .and_then(|object| object.peel_to_commit())
+        .with_context(|| format!("Cannot find the commit of tag {}", tag.name()))?;
+
+    let seconds = commit.time().seconds();
+
+    u64::try_from(seconds).context("Invalid commit time")
+}
+
+/// The sha of the checked out commit, `None` when files have uncommitted changes or untracked
+/// files, such as a new `build.rs` input, sit outside the output directories
+pub fn get_clean_head_sha(
+    repo_path: impl AsRef<Path>,
+    output_dirs: &[&Path],
+) -> Result<Option<String>> {
+    let repo = Repository::open(repo_path).context("Cannot read repo info")?;
+    let workdir = repo.workdir().map(Path::to_path_buf).unwrap_or_default();
+    let output_dirs: Vec<&Path> = output_dirs
+        .iter()
+        .map(|dir| dir.strip_prefix(&workdir).unwrap_or(dir))
+        .collect();
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).include_ignored(false);
+    let changed = repo.statuses(Some(&mut options))?.iter().any(|entry| {
+        let path = Path::new(entry.path().unwrap_or_default());
+        !(entry.status() == Status::WT_NEW && output_dirs.iter().any(|dir| path.starts_with(dir)))
+    });
+    if changed {
+        return Ok(None);
+    }
+
+    let commit = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .context("Cannot find the checked out commit")?;
+
+    Ok(Some(commit.id().to_string()))
+}
+
+/// Writes the tree of the tagged commit into `dir`, keeping the executable bits and symlinks.
+/// Submodules are not part of the tree and are left out
+pub fn export_tree(repo_path: impl AsRef<Path>, tag: &Tag, dir: impl AsRef<Path>) -> Result<()> {
+    let repo = Repository::open(repo_path).context("Cannot read repo info")?;
+    let tree = repo
+        .revparse_single(tag.name())
+        .and_then(|object| object.peel_to_tree())
+        .with_context(|| format!("Cannot find the tree of tag {}", tag.name()))?;
+
+    let mut entries = vec![];
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let path = PathBuf::from(root).join(entry.name().unwrap_or_default());
+        entries.push((path, entry.id(), entry.filemode(), entry.kind()));
+        TreeWalkResult::Ok
+    })?;
+
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    for (path, id, filemode, kind) in entries {
+        let target = dir.join(&path);
+        match kind {
+            Some(ObjectType::Tree) => fs::create_dir_all(&target)?,
+            Some(ObjectType::Blob) => {
+                let blob = repo.find_blob(id)?;
+                write_blob(&target, blob.content(), filemode)
+                    .with_context(|| format!("Cannot write {}", target.display()))?;
+            }
+            _ => log::warn!("leaving out the submodule {}", path.display()),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_blob(path: &Path, content: &[u8], filemode: i32) -> Result<()> {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    if filemode == FILEMODE_LINK {
+        symlink(String::from_utf8_lossy(content).as_ref(), path)?;
+        return Ok(());
+    }
+
+    fs::write(path, content)?;
+    let mode = if filemode == FILEMODE_EXECUTABLE {
+        0o755
+    } else {
+        0o644
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+
+    Ok(())
+}
+
+/// Symlinks are written as files holding their target, like git does without symlink support
+#[cfg(not(unix))]
+fn write_blob(path: &Path, content: &[u8], _filemode: i32) -> Result<()> {
+    fs::write(path, content)?;
+    Ok(())
+}
+
 // Get the current working directory (always pointing to ".")
 #[macro_export]
 macro_rules! cwd {
@@ -135,6 +246,94 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_get_commit_time() -> Result<(), Box<dyn std::error::Error>> {
+        let (tmp, repo) = init_repo()?;
+
+        commit!(repo, "Initial commit");
+        tag!(repo, "v1.0.0");
+
+        let expected = repo.head()?.peel_to_commit()?.time().seconds();
+
+        let time = get_commit_time(tmp.path(), &Tag::new("v1.0.0"))?;
+
+        assert_eq!(time as i64, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_clean_head_sha() -> Result<(), Box<dyn std::error::Error>> {
+        let (tmp, repo) = init_repo()?;
+        let path = tmp.path();
+
+        fs::write(path.join("test.txt"), "Hello, world!")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("test.txt"))?;
+        index.add_path(Path::new(".gitconfig"))?;
+        index.write()?;
+        commit!(repo, "Initial commit");
+
+        let sha = repo.head()?.peel_to_commit()?.id().to_string();
+        let dist = Path::new("dist");
+        assert_eq!(get_clean_head_sha(path, &[dist])?, Some(sha.to_owned()));
+
+        fs::create_dir(path.join("dist"))?;
+        fs::write(path.join("dist").join("tool.tar.gz"), "archive")?;
+        assert_eq!(get_clean_head_sha(path, &[dist])?, Some(sha));
+
+        fs::write(path.join("build.rs"), "fn main() {}")?;
+        assert_eq!(get_clean_head_sha(path, &[dist])?, None);
+        fs::remove_file(path.join("build.rs"))?;
+
+        fs::write(path.join("test.txt"), "Goodbye, world!")?;
+        assert_eq!(get_clean_head_sha(path, &[dist])?, None);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_export_tree() -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (tmp, repo) = init_repo()?;
+        let path = tmp.path();
+
+        fs::create_dir(path.join("scripts"))?;
+        fs::write(path.join("test.txt"), "Hello, world!")?;
+        fs::write(path.join("scripts").join("run.sh"), "#!/bin/sh")?;
+        fs::set_permissions(
+            path.join("scripts").join("run.sh"),
+            fs::Permissions::from_mode(0o755),
+        )?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("test.txt"))?;
+        index.add_path(Path::new("scripts/run.sh"))?;
+        index.write()?;
+        commit!(repo, "Initial commit");
+        tag!(repo, "v1.0.0");
+
+        fs::write(path.join("test.txt"), "Goodbye, world!")?;
+        fs::write(path.join("untracked.txt"), "untracked")?;
+
+        let export = TempDir::new("export")?;
+        export_tree(path, &Tag::new("v1.0.0"), export.path())?;
+
+        assert_eq!(
+            fs::read_to_string(export.path().join("test.txt"))?,
+            "Hello, world!"
+        );
+        let mode = fs::metadata(export.path().join("scripts").join("run.sh"))?
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(!export.path().join("untracked.txt").exists());
+        assert!(!export.path().join(".gitconfig").exists());
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_current_tag_no_tags() -> Result<(), Box<dyn std::error::Error>> {
         let (path, _) = init_repo()?;
@@ -2,7 +2,6 @@ use super::asset::{Asset, UploadedAsset};
 use crate::{
     brew::package::Package,
     build::{arch::Arch, os::Os},
-    compression::Compression,
 };
 use anyhow::Context;
 use std::ops::{Deref, DerefMut};
@@ -21,22 +20,12 @@ impl<'matrix> AssetMatrixEntry<'matrix> {
         arch: &'matrix Arch,
         os: &'matrix Os,
         name: impl Into<String>,
-        tag: &'matrix str,
-        compression: &'matrix Compression,
         prebuilt: bool,
     ) -> Self {
-        let name = format!(
-            "{}-{}-{}-{}.{}",
-            name.into(),
-            tag,
-            arch.to_string(),
-            os.to_string(),
-            compression.extension()
-        );
         Self {
             arch,
             os,
-            name,
+            name: name.into(),
             asset: None,
             prebuilt,
         }
@@ -77,7 +66,7 @@ impl<'matrix> EnrichedMatrixEntry<'matrix> {
 pub struct AssetMatrix<'matrix>(Vec<AssetMatrixEntry<'matrix>>);
 
 impl AssetMatrix<'_> {
-    pub fn enrich(&self, uploaded_assets: Vec<UploadedAsset>) -> Vec<EnrichedMatrixEntry> {
+    pub fn enrich(&self, uploaded_assets: Vec<UploadedAsset>) -> Vec<EnrichedMatrixEntry<'_>> {
         self.iter()
             .map(|entry| {
                 let uploaded_asset = uploaded_assets
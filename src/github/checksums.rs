@@ -0,0 +1,223 @@
+use super::asset::Asset;
+use crate::{
+    build::Build,
+    checksum::{Checksum, ChecksumAlgorithm},
+    config::{ChecksumMode, ReleaseConfig},
+    git::tag::Tag,
+    naming::{check_unique, ReleaseContext},
+};
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The checksum files of the release assets, written to the dist directory
+#[derive(Debug, Clone)]
+pub struct ChecksumFiles {
+    mode: ChecksumMode,
+    dir: PathBuf,
+    /// Every algorithm, with the name of its combined file
+    algorithms: Vec<(ChecksumAlgorithm, String)>,
+}
+
+/// The checksums of an asset, with every configured algorithm
+#[derive(Debug, Clone)]
+pub struct AssetChecksums {
+    name: String,
+    checksums: Vec<Checksum>,
+}
+
+impl ChecksumFiles {
+    pub fn new(build: &Build, release_config: &ReleaseConfig, tag: &Tag) -> Result<Self> {
+        let context = ReleaseContext::new(build.name(), tag);
+        let config = &release_config.checksum;
+
+        let algorithms = config
+            .algorithms()
+            .into_iter()
+            .map(|algorithm| Ok((algorithm, config.file_name(&context, algorithm)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if config.mode.combined() {
+            check_unique(algorithms.iter().map(|(_, name)| name))?;
+        }
+
+        Ok(ChecksumFiles {
+            mode: config.mode.to_owned(),
+            dir: build.dist_dir().to_owned(),
+            algorithms,
+        })
+    }
+
+    /// Hashes the asset with every algorithm, reusing the SHA-256 computed while writing it
+    pub fn checksums(&self, asset: &Asset) -> Result<AssetChecksums> {
+        let missing: Vec<ChecksumAlgorithm> = self
+            .algorithms
+            .iter()
+            .map(|(algorithm, _)| *algorithm)
+            .filter(|algorithm| *algorithm != ChecksumAlgorithm::Sha256 || asset.checksum.is_none())
+            .collect();
+        let mut computed = Checksum::all(&asset.path, &missing)
+            .with_context(|| format!("Cannot hash {}", asset.name))?
+            .into_iter();
+
+        let checksums = self
+            .algorithms
+            .iter()
+            .map(|(algorithm, _)| match (algorithm, &asset.checksum) {
+                (ChecksumAlgorithm::Sha256, Some(sha256)) => {
+                    Ok(Checksum::from_value(*algorithm, sha256))
+                }
+                _ => computed.next().context("Missing checksum"),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(AssetChecksums {
+            name: asset.name.to_owned(),
+            checksums,
+        })
+    }
+
+    /// The `<asset>.<algorithm>` files of the asset, unless only the combined files are uploaded
+    pub fn per_asset(&self, checksums: &AssetChecksums) -> Result<Vec<Asset>> {
+        if !self.mode.per_asset() {
+            return Ok(vec![]);
+        }
+
+        checksums
+            .checksums
+            .iter()
+            .map(|checksum| {
+                let file_name = format!("{}.{}", checksums.name, checksum.algorithm());
+                let line = checksum_line(checksum, &checksums.name);
+                write(&self.dir, &file_name, line.trim_end())
+            })
+            .collect()
+    }
+
+    /// A file per algorithm listing the checksums of every asset, sorted by name, when uploaded
+    pub fn combined(&self, assets: &[AssetChecksums]) -> Result<Vec<Asset>> {
+        if !self.mode.combined() {
+            return Ok(vec![]);
+        }
+
+        let mut assets: Vec<&AssetChecksums> = assets.iter().collect();
+        assets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.algorithms
+            .iter()
+            .enumerate()
+            .map(|(index, (_, file_name))| {
+                let content: String = assets
+                    .iter()
+                    .map(|asset| checksum_line(&asset.checksums[index], &asset.name))
+                    .collect();
+                write(&self.dir, file_name, &content)
+            })
+            .collect()
+    }
+}
+
+/// The checksum of the asset in the format of `sha256sum` and the like
+fn checksum_line(checksum: &Checksum, name: &str) -> String {
+    format!("{}  {}\n", checksum.value(), name)
+}
+
+fn write(dir: &Path, file_name: &str, content: &str) -> Result<Asset> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(file_name);
+    fs::write(&path, content).with_context(|| format!("Cannot write {}", path.display()))?;
+
+    Ok(Asset::new(file_name, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn asset(dir: &Path, name: &str, content: &str) -> Result<Asset> {
+        let path = dir.join(name);
+        fs::write(&path, content)?;
+
+        let mut asset = Asset::new(name, path);
+        asset.add_checksum(Checksum::new(&asset.path)?.value());
+        Ok(asset)
+    }
+
+    fn checksum_files(dir: &Path, mode: ChecksumMode) -> ChecksumFiles {
+        ChecksumFiles {
+            mode,
+            dir: dir.join("dist"),
+            algorithms: vec![
+                (ChecksumAlgorithm::Sha256, "SHA256SUMS".to_owned()),
+                (ChecksumAlgorithm::Sha1, "SHA1SUMS".to_owned()),
+            ],
+        }
+    }
+
+    #[test]
+    fn should_write_a_combined_file_per_algorithm_sorted_by_name() -> Result<()> {
+        let dir = TempDir::new("checksums")?;
+        let files = checksum_files(dir.path(), ChecksumMode::Combined);
+        let checksums = [
+            files.checksums(&asset(dir.path(), "tool-x86_64.tar.gz", "x86_64")?)?,
+            files.checksums(&asset(dir.path(), "tool-aarch64.tar.gz", "aarch64")?)?,
+        ];
+        assert!(files.per_asset(&checksums[0])?.is_empty());
+
+        let combined = files.combined(&checksums)?;
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].name, "SHA256SUMS");
+        assert_eq!(combined[0].path, dir.path().join("dist").join("SHA256SUMS"));
+
+        let sha1 = |content: &str| -> Result<String> {
+            fs::write(dir.path().join("content"), content)?;
+            let checksum =
+                Checksum::with_algorithm(dir.path().join("content"), ChecksumAlgorithm::Sha1)?;
+            Ok(checksum.value().to_owned())
+        };
+        assert_eq!(
+            fs::read_to_string(&combined[1].path)?,
+            format!(
+                "{}  tool-aarch64.tar.gz\n{}  tool-x86_64.tar.gz\n",
+                sha1("aarch64")?,
+                sha1("x86_64")?
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_write_the_per_asset_files_into_the_dist_dir() -> Result<()> {
+        let dir = TempDir::new("checksums")?;
+        let files = checksum_files(dir.path(), ChecksumMode::PerAsset);
+        let mut asset = asset(dir.path(), "tool.zip", "zip")?;
+        let sha256 = asset.checksum.to_owned().unwrap();
+
+        let checksums = files.checksums(&asset)?;
+        let per_asset = files.per_asset(&checksums)?;
+        assert_eq!(
+            per_asset
+                .iter()
+                .map(|asset| &asset.name)
+                .collect::<Vec<_>>(),
+            ["tool.zip.sha256", "tool.zip.sha1"]
+        );
+        assert_eq!(
+            fs::read_to_string(&per_asset[0].path)?,
+            format!("{}  tool.zip", sha256)
+        );
+        assert!(files.combined(&[checksums])?.is_empty());
+
+        // the SHA-256 is computed when the asset does not come with it
+        asset.checksum = None;
+        let both = checksum_files(dir.path(), ChecksumMode::Both);
+        let checksums = both.checksums(&asset)?;
+        assert_eq!(checksums.checksums[0].value(), sha256);
+        assert_eq!(both.combined(&[checksums])?.len(), 2);
+
+        Ok(())
+    }
+}
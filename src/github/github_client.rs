@@ -6,7 +6,8 @@ use super::{
         pull_request_request::PullRequestRequest,
     },
     response::{
-        pull_request_response::PullRequest, release_response::ReleaseResponse, sha_response::Sha,
+        pull_request_response::PullRequest, release_assets_response::ReleaseAssetsResponse,
+        release_response::ReleaseResponse, sha_response::Sha,
     },
 };
 use crate::{
@@ -38,6 +39,7 @@ pub fn instance() -> &'static GithubClient {
 const GITHUB_DEFAULT_URL: &str = "https://github.com";
 const GITHUB_API_REPO_URL: &str = "https://api.github.com/repos";
 const GITHUB_API_UPLOAD_URL: &str = "https://uploads.github.com/repos";
+const RELEASES_PAGE_SIZE: usize = 100;
 
 pub struct GithubClient;
 
@@ -271,6 +273,33 @@ impl GithubClient {
         Ok(Release::new(release.id, owner, repo))
     }
 
+    /// Every release of the repository, newest first, reading the pages until a short one
+    pub(super) async fn list_releases(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Vec<ReleaseAssetsResponse>> {
+        let mut releases = vec![];
+
+        for page in 1.. {
+            let uri = format!(
+                "{}/{}/{}/releases?per_page={}&page={}",
+                GITHUB_API_REPO_URL, owner, repo, RELEASES_PAGE_SIZE, page
+            );
+
+            let response = get!(&uri)?;
+            let page = serde_json::from_str::<Vec<ReleaseAssetsResponse>>(&response)?;
+            let last_page = page.len() < RELEASES_PAGE_SIZE;
+            releases.extend(page);
+
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(releases)
+    }
+
     async fn set_pr_assignees(
         &self,
         owner: impl Into<String>,
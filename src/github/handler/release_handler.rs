@@ -1,11 +1,13 @@
 use crate::{
+    artifacts::PreviousRelease,
     git::tag::Tag,
     github::{
         github_client, handler::builder::create_release_builder::CreateReleaseBuilder,
-        release::Release,
+        release::Release, response::release_assets_response::ReleaseAssetsResponse,
     },
 };
 use anyhow::Result;
+use semver::Version;
 
 pub struct ReleaseHandler {
     owner: String,
@@ -29,4 +31,87 @@ impl ReleaseHandler {
             .get_release_by_tag(&self.owner, &self.repo, tag)
             .await
     }
+
+    /// The published release with the highest version below the given tag, with its asset
+    /// sizes, so that a patch of an older branch is compared with its own line
+    pub async fn previous(&self, tag: &Tag) -> Result<Option<PreviousRelease>> {
+        let releases = github_client::instance()
+            .list_releases(&self.owner, &self.repo)
+            .await?;
+
+        let previous = previous_release(releases, tag).map(|release| PreviousRelease {
+            tag: Tag::new(release.tag_name),
+            asset_sizes: release
+                .assets
+                .into_iter()
+                .map(|asset| (asset.name, asset.size))
+                .collect(),
+        });
+
+        Ok(previous)
+    }
+}
+
+fn previous_release(
+    releases: Vec<ReleaseAssetsResponse>,
+    tag: &Tag,
+) -> Option<ReleaseAssetsResponse> {
+    let Ok(current) = Version::parse(tag.strip_v_prefix()) else {
+        log::warn!(
+            "{} is not a semver tag, not looking for the previous release",
+            tag.name()
+        );
+        return None;
+    };
+
+    releases
+        .into_iter()
+        .filter(|release| !release.draft && !release.prerelease)
+        .filter_map(|release| {
+            Version::parse(Tag::new(&release.tag_name).strip_v_prefix())
+                .ok()
+                .filter(|version| *version < current)
+                .map(|version| (version, release))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag_name: &str, draft: bool, prerelease: bool) -> ReleaseAssetsResponse {
+        ReleaseAssetsResponse {
+            tag_name: tag_name.to_owned(),
+            draft,
+            prerelease,
+            assets: vec![],
+        }
+    }
+
+    #[test]
+    fn should_pick_the_highest_published_version_below_the_tag() {
+        let releases = || {
+            vec![
+                release("v2.1.0", false, false),
+                release("v2.0.0-rc.1", false, true),
+                release("v1.4.3", true, false),
+                release("v2.0.0", false, false),
+                release("nightly", false, false),
+                release("v1.4.1", false, false),
+                release("v1.4.2", false, false),
+            ]
+        };
+
+        let previous = |tag: &str| {
+            previous_release(releases(), &Tag::new(tag)).map(|release| release.tag_name)
+        };
+
+        assert_eq!(previous("v1.4.4"), Some("v1.4.2".to_owned()));
+        assert_eq!(previous("v2.0.1"), Some("v2.0.0".to_owned()));
+        assert_eq!(previous("v2.1.0"), Some("v2.0.0".to_owned()));
+        assert_eq!(previous("v1.0.0"), None);
+        assert_eq!(previous("nightly"), None);
+    }
 }
@@ -1,5 +1,6 @@
 pub mod asset;
 mod asset_matrix;
+mod checksums;
 mod dto;
 pub mod github_client;
 pub mod handler;
@@ -9,21 +10,39 @@ mod prebuilt;
 mod release;
 mod request;
 mod response;
+mod signatures;
 mod single;
 
 use self::release::Release;
 use crate::{
-    brew::package::Package, build::Build, compression::Compression, config::ReleaseConfig,
-    git::tag::Tag, github::asset::Asset,
+    artifacts::Artifacts,
+    brew::package::Package,
+    build::{arch::Arch, binary::Binary, os::Os, Build},
+    cargo::{self, arch_os_matrix::ArchOsMatrixEntry, build_log, LOGS_DIR_NAME},
+    checksum::ChecksumWriter,
+    compression::{compress_file, Compression, Contents, Layout},
+    config::{FileEntry, ReleaseConfig},
+    cwd, debuginfo,
+    git::{self, tag::Tag},
+    github::asset::Asset,
+    licenses,
+    naming::NameContext,
+    object_header::verify_binary,
+    self_test,
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use handler::BuilderExecutor;
 use std::{
     fs,
+    io::{self, BufWriter},
     path::{Path, PathBuf},
 };
 
-const SINGLE_TARGET_DIR: &str = "target/release";
+const DEBUG_ASSET_SUFFIX: &str = "debug";
+const BUILD_LOGS_ASSET_SUFFIX: &str = "build-logs";
+const SOURCE_ASSET_SUFFIX: &str = "src";
+const VENDORED_SOURCE_ASSET_SUFFIX: &str = "vendored";
+const SOURCE_DIR_NAME: &str = "source";
 
 pub async fn release(build: &Build, release_config: &ReleaseConfig) -> Result<Vec<Package>> {
     let packages = match build.target_type() {
@@ -37,26 +56,79 @@ pub async fn release(build: &Build, release_config: &ReleaseConfig) -> Result<Ve
         }
         crate::build::TargetType::PreBuilt => {
             log::debug!("Running prebuilt, ignoring build info");
-            prebuilt::release(build, release_config, &release_config.archive.compression).await?
+            prebuilt::release(build, release_config).await?
         }
     };
 
     Ok(packages)
 }
 
-fn check_binary(name: &str, target: Option<String>) -> Result<()> {
-    log::debug!("checking binary: {} - {:#?}", name, target);
-    let binary_path = if let Some(target) = target {
-        format!("target/{}/release/{}", target, name)
-    } else {
-        format!("{}/{}", SINGLE_TARGET_DIR, name)
+/// Checks that every binary exists and that its header matches the target
+fn check_binary(build: &Build, target: Option<&ArchOsMatrixEntry>) -> Result<()> {
+    let target_name = target.map(|target| target.to_string());
+    let (arch, os) = match target {
+        Some(target) => (Some(target.arch.to_owned()), target.os.to_owned()),
+        None => (Arch::host(), Os::host()),
     };
 
-    if !PathBuf::from(binary_path).exists() {
-        bail!(anyhow::anyhow!(
-            "no release folder found, please run `cargo build --release`"
-        ));
+    for binary in &build.binaries(&os) {
+        log::debug!("checking binary: {} - {:#?}", binary.name, target_name);
+        let binary_path = build
+            .binaries_dir(target_name.as_deref())
+            .join(&binary.name);
+
+        if !binary_path.exists() {
+            bail!(anyhow::anyhow!(
+                "no release folder found, please run `cargo build --release`"
+            ));
+        }
+
+        if let Some(arch) = &arch {
+            verify_binary(&binary_path, arch, &os, build.static_musl)?;
+        }
+    }
+    Ok(())
+}
+
+/// The archive entries date, fixed to the tag commit time on reproducible builds
+fn archive_mtime(build: &Build, tag: &Tag) -> Result<Option<u64>> {
+    if build.reproducible {
+        Ok(Some(git::get_commit_time(cwd!(), tag)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Reports the artifact sizes against the previous release and enforces the size budget
+async fn report_sizes(
+    build: &Build,
+    release_config: &ReleaseConfig,
+    artifacts: &mut Artifacts,
+    tag: &Tag,
+) -> Result<()> {
+    let previous = github_client::instance()
+        .repo(&release_config.owner, &release_config.repo)
+        .releases()
+        .previous(tag)
+        .await;
+
+    match previous {
+        Ok(Some(previous)) => {
+            log::info!("comparing sizes with release {}", previous.tag.name());
+            artifacts.compare(&previous, &release_config.archive);
+        }
+        Ok(None) => log::info!("no previous release to compare sizes with"),
+        Err(err) => log::warn!("cannot fetch the previous release: {:#}", err),
+    }
+
+    artifacts.print_sizes();
+    let manifest = artifacts.write(build.dist_dir())?;
+    log::debug!("artifacts manifest written to {}", manifest.display());
+
+    if let Some(budget) = &release_config.size_budget {
+        artifacts.check_budget(budget)?;
     }
+
     Ok(())
 }
 
@@ -106,27 +178,370 @@ async fn get_release(release_config: &ReleaseConfig, tag: &Tag) -> Result<Releas
     }
 }
 
-fn create_compressed_asset(
-    name: impl Into<String>,
-    path: impl AsRef<Path>,
+/// The asset of the binaries with its checksum: their archive, or the bare binary copied into
+/// the dist dir
+#[allow(clippy::too_many_arguments)]
+fn create_binary_asset(
+    binaries: &[Binary],
+    dir: impl AsRef<Path>,
+    dist_dir: &Path,
+    name: &str,
+    context: &NameContext,
+    os: &Os,
+    release_config: &ReleaseConfig,
+    mtime: Option<u64>,
+) -> Result<Asset> {
+    let compression = release_config.archive.compression(os);
+    let mut layout = release_config.archive.layout(context)?;
+    let mut expected = Contents::new(binaries, layout.directory.as_deref());
+    for file in release_config.archive.files.iter().flatten() {
+        expected.push_matches(&file.entry())?;
+    }
+    for doc in release_config.archive.bundled_docs(cwd!())? {
+        expected.push_file(doc);
+    }
+    if release_config.archive.third_party_licenses.is_some() && *compression != Compression::Binary
+    {
+        expected.push_file(licenses::THIRD_PARTY_LICENSES_FILE_NAME);
+        let path = licenses::path(dist_dir, &context.target);
+        if !path.is_file() {
+            bail!(anyhow::anyhow!(
+                "the third party licenses of {} were not generated, {} is missing",
+                context.target,
+                path.display()
+            ));
+        }
+        layout.push_file(path);
+    }
+    if *compression != Compression::Binary {
+        let asset = create_archive_asset(
+            binaries,
+            &dir,
+            dist_dir,
+            name,
+            &layout,
+            compression,
+            release_config.archive.level(os),
+            mtime,
+        )?;
+        self_test::check(&asset.path, compression, &expected)?;
+        self_test::smoke_test(
+            &asset.path,
+            compression,
+            layout.directory.as_deref(),
+            context,
+            release_config.archive.smoke_test.as_ref(),
+        )?;
+
+        return Ok(asset);
+    }
+
+    let [binary] = binaries else {
+        bail!(anyhow::anyhow!(
+            "the binary format uploads a single binary per asset, found {}",
+            binaries.len()
+        ));
+    };
+    if release_config.archive.files.is_some()
+        || release_config.archive.third_party_licenses.is_some()
+        || layout.directory.is_some()
+    {
+        log::warn!("the binary format ignores the archive files, licenses and directory");
+    }
+
+    let file_name = compression.file_name(name, os);
+    let path = dist_dir.join(&file_name);
+    fs::create_dir_all(dist_dir)?;
+    let mut source = fs::File::open(dir.as_ref().join(&binary.name))
+        .with_context(|| format!("Cannot open {}", binary.name))?;
+    let mut writer = ChecksumWriter::new(BufWriter::new(
+        fs::File::create(&path).with_context(|| format!("Cannot create {}", path.display()))?,
+    ));
+    io::copy(&mut source, &mut writer).with_context(|| format!("Cannot copy {}", binary.name))?;
+    let (_, checksum) = writer.finish()?;
+
+    let mut asset = Asset::new(&file_name, &path);
+    asset.add_checksum(checksum.value());
+
+    Ok(asset)
+}
+
+/// Archives the files into an asset under the dist dir, along with its checksum
+#[allow(clippy::too_many_arguments)]
+fn create_archive_asset(
+    files: &[Binary],
+    dir: impl AsRef<Path>,
+    dist_dir: &Path,
+    name: &str,
+    layout: &Layout,
+    compression: &Compression,
+    level: Option<u32>,
+    mtime: Option<u64>,
+) -> Result<Asset> {
+    let (compressed_file_path, checksum) = compress_file(
+        files,
+        dir,
+        dist_dir,
+        name,
+        layout,
+        compression,
+        level,
+        mtime,
+    )?;
+
+    let file_name = compressed_file_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .context("Invalid archive name")?;
+    let mut asset = Asset::new(file_name, compressed_file_path);
+    asset.add_checksum(checksum.value());
+
+    Ok(asset)
+}
+
+/// The archive format of files other than the binaries, such as debug info or logs
+fn support_archive(release_config: &ReleaseConfig, os: &Os) -> (Compression, Option<u32>) {
+    match release_config.archive.compression(os) {
+        Compression::Binary => (Compression::Binary.archive(), None),
+        compression => (compression.to_owned(), release_config.archive.level(os)),
+    }
+}
+
+/// Archives plain files at the root of a support asset, none of them being run
+fn create_support_asset(
+    files: &[PathBuf],
+    dist_dir: &Path,
+    name: &str,
     compression: &Compression,
-) -> Asset {
-    Asset::new(format!("{}.{}", name.into(), compression.extension()), path)
+    level: Option<u32>,
+    mtime: Option<u64>,
+) -> Result<Asset> {
+    let mut layout = Layout::default();
+    let mut expected = Contents::default();
+    for file in files {
+        layout.push_file(file);
+        expected.push_file(file);
+    }
+
+    let asset = create_archive_asset(
+        &[],
+        dist_dir,
+        dist_dir,
+        name,
+        &layout,
+        compression,
+        level,
+        mtime,
+    )?;
+    self_test::check(&asset.path, compression, &expected)?;
+
+    Ok(asset)
 }
 
-fn generate_checksum_asset(asset: &Asset) -> Result<Asset> {
-    if let Some(checksum) = &asset.checksum {
-        let sha256_file_name = format!("{}.sha256", asset.name);
+/// Archives the split debug info of a target, when the build keeps it
+fn create_debug_asset(
+    build: &Build,
+    target: Option<&str>,
+    os: &Os,
+    name: &str,
+    release_config: &ReleaseConfig,
+    mtime: Option<u64>,
+) -> Result<Option<Asset>> {
+    if !build
+        .strip
+        .as_ref()
+        .is_some_and(|strip| strip.split_debug_info)
+        || !debuginfo::is_supported(os)
+    {
+        return Ok(None);
+    }
 
-        let path = PathBuf::from(&sha256_file_name);
-        fs::write(&path, format!("{}  {}", checksum, asset.name))?;
+    let (compression, level) = support_archive(release_config, os);
+    let dir = build.binaries_dir(target);
+    let files: Vec<PathBuf> = debuginfo::debug_files(&build.binary, os)
+        .into_iter()
+        .map(|file| dir.join(file))
+        .collect();
+    let asset = create_support_asset(
+        &files,
+        build.dist_dir(),
+        &format!("{}-{}", name, DEBUG_ASSET_SUFFIX),
+        &compression,
+        level,
+        mtime,
+    )?;
 
-        let asset = Asset::new(&sha256_file_name, path);
-        Ok(asset)
-    } else {
-        bail!(anyhow::anyhow!(
-            "checksum is not available for asset {:#?}",
-            asset
-        ))
+    Ok(Some(asset))
+}
+
+/// Archives the build logs of every target, when the release uploads them
+fn create_build_logs_asset(
+    build: &Build,
+    release_config: &ReleaseConfig,
+    tag: &Tag,
+    mtime: Option<u64>,
+) -> Result<Option<Asset>> {
+    if !release_config.upload_build_logs {
+        return Ok(None);
+    }
+
+    let dir = build.dist_dir().join(LOGS_DIR_NAME);
+    let files: Vec<PathBuf> = cargo::targets(build)
+        .into_iter()
+        .map(|target| {
+            let target = target.map(|entry| entry.to_string());
+            dir.join(build_log::log_file_name(target.as_deref()))
+        })
+        .collect();
+
+    let (compression, level) = support_archive(release_config, &Os::host());
+    let asset = create_support_asset(
+        &files,
+        build.dist_dir(),
+        &format!(
+            "{}-{}-{}",
+            build.name(),
+            tag.name(),
+            BUILD_LOGS_ASSET_SUFFIX
+        ),
+        &compression,
+        level,
+        mtime,
+    )?;
+
+    Ok(Some(asset))
+}
+
+/// The source tree, under its own directory
+fn source_contents(source_dir: &Path) -> Result<Contents> {
+    let mut expected = Contents::default();
+    expected.push_matches(&FileEntry {
+        src: glob::Pattern::escape(&source_dir.to_string_lossy()),
+        strip_parent: true,
+        ..Default::default()
+    })?;
+
+    Ok(expected)
+}
+
+/// Archives the source tree of the tag, and along with the vendored dependencies,
+/// when the release uploads them
+async fn create_source_assets(
+    build: &Build,
+    release_config: &ReleaseConfig,
+    tag: &Tag,
+) -> Result<Vec<Asset>> {
+    if !release_config.upload_source && !release_config.upload_vendored_source {
+        return Ok(vec![]);
+    }
+
+    let name = format!("{}-{}", build.name(), tag.strip_v_prefix());
+    let staging_dir = build.dist_dir().join(SOURCE_DIR_NAME);
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)?;
+    }
+    let source_dir = staging_dir.join(&name);
+    log::info!("exporting the source of {}", tag.name());
+    git::export_tree(cwd!(), tag, &source_dir)?;
+
+    // the source archives are always reproducible, dated from the tag commit
+    let mtime = git::get_commit_time(cwd!(), tag)?;
+    let mut layout = Layout::default();
+    layout.push_file(&source_dir);
+
+    let mut assets = vec![];
+    if release_config.upload_source {
+        let asset = create_archive_asset(
+            &[],
+            &staging_dir,
+            build.dist_dir(),
+            &format!("{}-{}", name, SOURCE_ASSET_SUFFIX),
+            &layout,
+            &Compression::TarGz,
+            None,
+            Some(mtime),
+        )?;
+        self_test::check(
+            &asset.path,
+            &Compression::TarGz,
+            &source_contents(&source_dir)?,
+        )?;
+        assets.push(asset);
+    }
+    if release_config.upload_vendored_source {
+        cargo::vendor::vendor(&source_dir).await?;
+        let asset = create_archive_asset(
+            &[],
+            &staging_dir,
+            build.dist_dir(),
+            &format!("{}-{}", name, VENDORED_SOURCE_ASSET_SUFFIX),
+            &layout,
+            &Compression::TarGz,
+            None,
+            Some(mtime),
+        )?;
+        self_test::check(
+            &asset.path,
+            &Compression::TarGz,
+            &source_contents(&source_dir)?,
+        )?;
+        assets.push(asset);
+    }
+
+    Ok(assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        build::arch::Arch,
+        cargo::{cache::BuildCache, restore_cached},
+        compression,
+    };
+    use tempdir::TempDir;
+
+    #[test]
+    fn should_archive_the_logs_of_targets_restored_from_the_cache() -> Result<()> {
+        let dir = TempDir::new("github")?;
+        let entry = ArchOsMatrixEntry::new(&Arch::Amd64, &Os::UnknownLinuxGnu);
+        let build = Build {
+            binary: vec![Binary::new("tool")],
+            arch: Some(vec![Arch::Amd64]),
+            os: Some(vec![Os::UnknownLinuxGnu]),
+            dist: Some(dir.path().join("dist")),
+            target_dir: Some(dir.path().join("target")),
+            ..Default::default()
+        };
+        let release_config: ReleaseConfig = serde_yaml::from_str("upload_build_logs: true")?;
+        let target = entry.to_string();
+        let release_dir = build.release_dir(Some(&target));
+        fs::create_dir_all(&release_dir)?;
+        fs::write(release_dir.join("tool"), "binary")?;
+
+        let cache = BuildCache::new(build.dist_dir(), "abc", &build, "rustc 1.0.0");
+        cache.store(&build, Some(&entry))?;
+        let log_path = build
+            .dist_dir()
+            .join(LOGS_DIR_NAME)
+            .join(build_log::log_file_name(Some(&target)));
+        assert!(restore_cached(
+            Some(&cache),
+            &build,
+            Some(&entry),
+            &log_path
+        )?);
+
+        let asset = create_build_logs_asset(&build, &release_config, &Tag::new("v1.0.0"), None)?
+            .context("the build logs asset is missing")?;
+        let (compression, _) = support_archive(&release_config, &Os::host());
+        let extracted = dir.path().join("extracted");
+        compression::extract(&asset.path, &compression, &extracted)?;
+
+        let log = fs::read_to_string(extracted.join(build_log::log_file_name(Some(&target))))?;
+        assert!(log.contains("restored from the build cache"));
+        assert!(log.contains(&cache.fingerprint(Some(&entry))));
+
+        Ok(())
     }
 }
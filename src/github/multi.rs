@@ -1,77 +1,202 @@
 use super::{
-    asset::Assets,
+    archive_mtime,
+    asset::{Asset, Assets},
     asset_matrix::{AssetMatrix, AssetMatrixEntry},
-    check_binary, create_compressed_asset, get_release,
+    check_binary,
+    checksums::ChecksumFiles,
+    create_binary_asset, create_build_logs_asset, create_debug_asset, create_source_assets,
+    get_release, report_sizes,
+    signatures::Signatures,
 };
 use crate::{
-    brew::package::Package, build::Build, checksum::Checksum, compression::compress_file,
-    config::ReleaseConfig, cwd, git,
+    artifacts::{Artifact, Artifacts},
+    brew::package::Package,
+    build::{os::Os, Build},
+    cargo::arch_os_matrix::ArchOsMatrixEntry,
+    config::ReleaseConfig,
+    cwd, git,
+    naming::{check_unique, NameContext},
+    universal::UNIVERSAL_TARGET,
 };
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use tokio::task;
 
 pub async fn release(build: &Build, release_config: &ReleaseConfig) -> Result<Vec<Package>> {
     let tag = git::get_current_tag(cwd!())?;
+    let mtime = archive_mtime(build, &tag)?;
+    let checksums = ChecksumFiles::new(build, release_config, &tag)?;
+    let signatures = Signatures::new(build, release_config)?;
 
-    let archs = build.arch.to_owned().unwrap_or_default();
-    let os = build.os.to_owned().unwrap_or_default();
+    let targets = Vec::<ArchOsMatrixEntry>::from(build.to_owned());
     let mut matrix: AssetMatrix = AssetMatrix::default();
+    let mut debug_assets = vec![];
+    let mut artifacts = Artifacts::new(&tag);
 
-    for arch in &archs {
-        for os in &os {
-            let binary = build.binary.to_owned();
-            check_binary(
-                &binary,
-                Some(format!("{}-{}", &arch.to_string(), &os.to_string())),
-            )?;
-
-            let mut entry = AssetMatrixEntry::new(
-                arch,
-                os,
-                binary,
-                tag.name(),
-                &release_config.archive.compression,
-                false,
-            );
-
-            let target = format!("{}-{}", &arch.to_string(), &os.to_string());
-
-            let entry_name = entry.name.to_owned();
-
-            let compressed_file_path = compress_file(
-                &build.binary.to_owned(),
-                PathBuf::from(format!("target/{}/release/{}", target, build.binary)),
-                &entry_name,
-                &release_config.archive.files,
-                &release_config.archive.compression,
-            )?;
-
-            let mut asset = create_compressed_asset(
-                &entry.name,
-                compressed_file_path,
-                &release_config.archive.compression,
-            );
-            let checksum = Checksum::try_from(&asset)
-                .unwrap_or_else(|_| panic!("Failed to generate checksum for asset {:#?}", asset));
-
-            asset.add_checksum(checksum.value());
-            entry.set_asset(asset);
-            matrix.push(entry);
-        }
+    for target in &targets {
+        check_binary(build, Some(target))?;
+    }
+
+    let archive_names = targets
+        .iter()
+        .map(|target| {
+            let compression = release_config.archive.compression(&target.os);
+            let context = NameContext::for_target(build.name(), &tag, target, compression)
+                .with_binary(build.binary_name());
+            let name = release_config.archive.asset_name(&context)?;
+            Ok((context, name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let universal_name = if build.universal {
+        let compression = release_config.archive.compression(&Os::AppleDarwin);
+        let context = NameContext::for_universal(build.name(), &tag, compression)
+            .with_binary(build.binary_name());
+        let name = release_config.archive.asset_name(&context)?;
+        Some((context, name))
+    } else {
+        None
+    };
+    check_unique(
+        targets
+            .iter()
+            .zip(&archive_names)
+            .map(|(target, (_, name))| (&target.os, name))
+            .chain(
+                universal_name
+                    .iter()
+                    .map(|(_, name)| (&Os::AppleDarwin, name)),
+            )
+            .map(|(os, name)| release_config.archive.compression(os).file_name(name, os)),
+    )?;
+
+    // every archive is written on its own blocking thread, so the targets are archived in parallel
+    let mut archives = vec![];
+    for (target, (context, archive_name)) in targets.iter().zip(archive_names.to_owned()) {
+        let (build, release_config, target) = (
+            build.to_owned(),
+            release_config.to_owned(),
+            target.to_owned(),
+        );
+
+        archives.push(task::spawn_blocking(
+            move || -> Result<(Asset, Option<Asset>)> {
+                let target_name = target.to_string();
+                let asset = create_binary_asset(
+                    &build.binaries(&target.os),
+                    build.binaries_dir(Some(&target_name)),
+                    build.dist_dir(),
+                    &archive_name,
+                    &context,
+                    &target.os,
+                    &release_config,
+                    mtime,
+                )?;
+                let debug_asset = create_debug_asset(
+                    &build,
+                    Some(&target_name),
+                    &target.os,
+                    &archive_name,
+                    &release_config,
+                    mtime,
+                )?;
+
+                Ok((asset, debug_asset))
+            },
+        ));
     }
 
+    let universal_archive = universal_name.to_owned().map(|(context, archive_name)| {
+        let (build, release_config) = (build.to_owned(), release_config.to_owned());
+
+        task::spawn_blocking(move || {
+            create_binary_asset(
+                &build.binary,
+                build.binaries_dir(Some(UNIVERSAL_TARGET)),
+                build.dist_dir(),
+                &archive_name,
+                &context,
+                &Os::AppleDarwin,
+                &release_config,
+                mtime,
+            )
+        })
+    });
+
+    for ((target, (context, _)), archive) in targets.iter().zip(&archive_names).zip(archives) {
+        let target_name = target.to_string();
+        let (asset, debug_asset) = archive.await??;
+
+        let mut entry = AssetMatrixEntry::new(&target.arch, &target.os, &asset.name, false);
+        artifacts.push(Artifact::new(
+            &asset,
+            Some(target_name.to_owned()),
+            context,
+            build
+                .binaries(&target.os)
+                .iter()
+                .map(|binary| build.binaries_dir(Some(&target_name)).join(&binary.name)),
+        )?);
+        entry.set_asset(asset);
+        matrix.push(entry);
+        debug_assets.extend(debug_asset);
+    }
+
+    let universal_asset = match universal_archive.zip(universal_name) {
+        Some((archive, (context, _))) => {
+            let asset = archive.await??;
+            artifacts.push(Artifact::new(
+                &asset,
+                Some(UNIVERSAL_TARGET.to_owned()),
+                &context,
+                build.binary.iter().map(|binary| {
+                    build
+                        .binaries_dir(Some(UNIVERSAL_TARGET))
+                        .join(&binary.name)
+                }),
+            )?);
+
+            Some(asset)
+        }
+        None => None,
+    };
+
+    report_sizes(build, release_config, &mut artifacts, &tag).await?;
+
     let release = get_release(release_config, &tag).await?;
 
-    let assets: Assets = Assets::from(&matrix);
+    let mut assets: Assets = Assets::from(&matrix);
+    let universal_name = universal_asset.as_ref().map(|asset| asset.name.to_owned());
+    assets.extend(universal_asset);
+    assets.extend(debug_assets);
+    assets.extend(create_build_logs_asset(build, release_config, &tag, mtime)?);
+    assets.extend(create_source_assets(build, release_config, &tag).await?);
 
-    let uploaded_assets = release.upload_assets(assets, &tag).await?;
+    let uploaded_assets = release
+        .upload_assets(assets, &tag, &checksums, signatures.as_ref())
+        .await?;
 
-    let packages: Vec<Package> = matrix
-        .enrich(uploaded_assets)
+    let mut packages: Vec<Package> = matrix
+        .enrich(uploaded_assets.to_owned())
         .iter()
         .cloned()
         .map(|e| e.into_package())
         .collect();
 
+    // the universal package has no arch, as it runs on both darwin CPU types
+    if let Some(universal_name) = universal_name {
+        let uploaded = uploaded_assets
+            .iter()
+            .find(|asset| asset.name == universal_name)
+            .with_context(|| format!("Asset {} not found", universal_name))?;
+
+        packages.push(Package::new(
+            uploaded.name.to_owned(),
+            Some(Os::AppleDarwin),
+            None,
+            uploaded.url.to_owned(),
+            uploaded.checksum.to_owned(),
+            false,
+        ));
+    }
+
     Ok(packages)
 }
@@ -1,28 +1,36 @@
 use crate::{
+    artifacts::{Artifact, Artifacts},
     brew::package::Package,
-    build::Build,
-    checksum::Checksum,
-    compression::{compress_file, Compression},
+    build::{binary::Binary, Build},
+    cargo::arch_os_matrix::ArchOsMatrixEntry,
     config::ReleaseConfig,
     cwd, git,
     github::{
+        archive_mtime,
         asset::Asset,
         asset_matrix::{AssetMatrix, AssetMatrixEntry},
-        create_compressed_asset, get_release,
+        checksums::ChecksumFiles,
+        create_binary_asset, create_source_assets, get_release, report_sizes,
+        signatures::Signatures,
     },
+    naming::{check_unique, NameContext},
+    object_header::verify_binary,
 };
 use anyhow::{bail, Result};
+use std::path::Path;
+use tokio::task;
 
-pub async fn release(
-    build: &Build,
-    release_config: &ReleaseConfig,
-    compression: &Compression,
-) -> Result<Vec<Package>> {
+pub async fn release(build: &Build, release_config: &ReleaseConfig) -> Result<Vec<Package>> {
     let prebuilt_items = build.to_owned().prebuilt.unwrap_or_default();
     let mut matrix: AssetMatrix = AssetMatrix::default();
 
     let tag = git::get_current_tag(cwd!())?;
+    let mtime = archive_mtime(build, &tag)?;
+    let checksums = ChecksumFiles::new(build, release_config, &tag)?;
+    let signatures = Signatures::new(build, release_config)?;
+    let mut artifacts = Artifacts::new(&tag);
 
+    let mut jobs = vec![];
     for prebuilt in prebuilt_items.iter() {
         let path = prebuilt.path.to_owned();
         if path.is_dir() {
@@ -31,51 +39,71 @@ pub async fn release(
         }
         // TODO fix this
         let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+
+        let (arch, os) = prebuilt.arch_os()?;
+        verify_binary(&path, arch, os, build.static_musl)?;
+
+        let compression = release_config.archive.compression(os);
+        let context =
+            NameContext::for_target(&name, &tag, &ArchOsMatrixEntry::new(arch, os), compression);
+        let full_name = release_config.archive.asset_name(&context)?;
+
         log::debug!("creating matrix entry for {:#?}", name);
-        let mut entry = AssetMatrixEntry::new(
-            prebuilt.arch.as_ref().unwrap(),
-            prebuilt.os.as_ref().unwrap(),
-            &name,
-            tag.name(),
-            compression,
-            true,
-        );
-
-        let full_name = format!(
-            "{}-{}-{}-{}",
-            name,
-            tag.name(),
-            entry.arch.to_string(),
-            entry.os.to_string()
-        );
-        let compressed_file_path = compress_file(
-            &name,
-            path,
-            &full_name,
-            &release_config.archive.files,
-            compression,
-        )?;
+        let entry = AssetMatrixEntry::new(arch, os, compression.file_name(&full_name, os), true);
+
+        jobs.push((entry, path, name, full_name, context));
+    }
+    check_unique(jobs.iter().map(|(entry, ..)| &entry.name))?;
 
+    // every archive is written on its own blocking thread, so the binaries are archived in parallel
+    let mut archives = vec![];
+    for (entry, path, name, full_name, context) in jobs {
         log::debug!("creating asset for {:#?}", full_name);
-        let mut asset = create_compressed_asset(&full_name, compressed_file_path, compression);
+        let dir = path.parent().unwrap_or(Path::new(".")).to_owned();
+        let dist_dir = build.dist_dir().to_owned();
+        let (os, release_config) = (entry.os.to_owned(), release_config.to_owned());
+        let archive_context = context.to_owned();
+        let archive = task::spawn_blocking(move || {
+            create_binary_asset(
+                &[Binary::new(&name)],
+                dir,
+                &dist_dir,
+                &full_name,
+                &archive_context,
+                &os,
+                &release_config,
+                mtime,
+            )
+        });
+        archives.push((entry, path, context, archive));
+    }
 
+    for (mut entry, path, context, archive) in archives {
+        let asset = archive.await??;
         log::debug!("asset created: {:?}", asset);
 
-        log::debug!("generating checksum for {:#?}", full_name);
-        let checksum = Checksum::try_from(&asset)
-            .unwrap_or_else(|_| panic!("Failed to generate checksum for asset {:#?}", asset));
-
-        asset.add_checksum(checksum.value());
+        artifacts.push(Artifact::new(
+            &asset,
+            Some(format!("{}-{}", entry.arch, entry.os)),
+            &context,
+            [path.to_owned()],
+        )?);
         entry.set_asset(asset);
         matrix.push(entry);
     }
 
+    report_sizes(build, release_config, &mut artifacts, &tag).await?;
+
     let release = get_release(release_config, &tag).await?;
 
-    let assets: Vec<Asset> = Vec::<Asset>::from(&matrix);
+    let mut assets: Vec<Asset> = Vec::<Asset>::from(&matrix);
+    assets.extend(create_source_assets(build, release_config, &tag).await?);
 
     log::debug!("uploading asset");
-    let uploaded_assets = match release.upload_assets(assets, &tag).await {
+    let uploaded_assets = match release
+        .upload_assets(assets, &tag, &checksums, signatures.as_ref())
+        .await
+    {
         Ok(uploaded_assets) => uploaded_assets,
         Err(e) => {
             log::error!("Failed to upload asset {:#?}", e);
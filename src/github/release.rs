@@ -1,17 +1,16 @@
-use super::{asset::UploadedAsset, generate_checksum_asset};
+use super::{asset::UploadedAsset, checksums::ChecksumFiles, signatures::Signatures};
 use crate::{
-    brew::package::Package,
     git::tag::Tag,
     github::{asset::Asset, github_client},
+    naming::check_unique,
 };
-use anyhow::{bail, Result};
+use anyhow::Result;
 
 #[derive(Debug, Clone)]
 pub struct Release {
     pub owner: String,
     pub repo: String,
     pub id: u64,
-    pub packages: Vec<Package>,
 }
 
 impl Release {
@@ -20,33 +19,106 @@ impl Release {
             id,
             owner: owner.into(),
             repo: repo.into(),
-            packages: vec![],
         }
     }
 
-    pub async fn upload_assets(&self, assets: Vec<Asset>, tag: &Tag) -> Result<Vec<UploadedAsset>> {
-        let mut uploaded = vec![];
+    /// Uploads every asset along with its checksum files and signature,
+    /// the combined checksums files and their signatures coming last.
+    ///
+    /// Every file is prepared first, so the release fails before any upload when two of them
+    /// would have the same name
+    pub async fn upload_assets(
+        &self,
+        assets: Vec<Asset>,
+        tag: &Tag,
+        checksums: &ChecksumFiles,
+        signatures: Option<&Signatures>,
+    ) -> Result<Vec<UploadedAsset>> {
+        let mut uploads = vec![];
+        let mut all_checksums = vec![];
         for asset in assets {
-            let uploaded_asset = github_client::instance()
-                .upload_asset(&asset, &self.owner, tag, &self.repo, self.id)
-                .await?;
-            log::debug!("Uploaded asset: {:#?}", uploaded_asset);
-            uploaded.push(uploaded_asset);
-
-            if let Err(err) = self.upload_checksum_asset(&asset, tag).await {
-                bail!(err)
+            let asset_checksums = checksums.checksums(&asset)?;
+            let mut extra_assets = checksums.per_asset(&asset_checksums)?;
+            all_checksums.push(asset_checksums);
+            if let Some(signatures) = signatures {
+                extra_assets.push(signatures.sign(&asset)?);
+            }
+
+            uploads.push((asset, true));
+            uploads.extend(extra_assets.into_iter().map(|asset| (asset, false)));
+        }
+
+        for checksum_asset in checksums.combined(&all_checksums)? {
+            let signature = match signatures {
+                Some(signatures) => Some(signatures.sign(&checksum_asset)?),
+                None => None,
+            };
+            uploads.push((checksum_asset, false));
+            uploads.extend(signature.map(|signature| (signature, false)));
+        }
+
+        check_unique(uploads.iter().map(|(asset, _)| &asset.name))?;
+
+        let mut uploaded = vec![];
+        for (asset, primary) in uploads {
+            if primary {
+                let uploaded_asset = github_client::instance()
+                    .upload_asset(&asset, &self.owner, tag, &self.repo, self.id)
+                    .await?;
+                log::debug!("Uploaded asset: {:#?}", uploaded_asset);
+                uploaded.push(uploaded_asset);
+            } else {
+                self.upload_checksum_asset(&asset, tag).await?;
             }
         }
 
         Ok(uploaded)
     }
 
-    async fn upload_checksum_asset(&self, asset: &Asset, tag: &Tag) -> Result<()> {
-        let checksum_asset = generate_checksum_asset(asset)?;
+    async fn upload_checksum_asset(&self, checksum_asset: &Asset, tag: &Tag) -> Result<()> {
         let ua = github_client::instance()
-            .upload_asset(&checksum_asset, &self.owner, tag, &self.repo, self.id)
+            .upload_asset(checksum_asset, &self.owner, tag, &self.repo, self.id)
             .await?;
         log::debug!("Uploaded checksum asset: {:#?}", ua);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        build::{binary::Binary, Build},
+        config::ReleaseConfig,
+    };
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn should_fail_before_uploading_when_two_assets_have_the_same_name() -> Result<()> {
+        let dir = TempDir::new("release")?;
+        let build = Build {
+            binary: vec![Binary::new("tool")],
+            dist: Some(dir.path().join("dist")),
+            ..Default::default()
+        };
+        let release_config: ReleaseConfig = serde_yaml::from_str("checksum:\n  mode: per-asset")?;
+        let tag = Tag::new("v1.0.0");
+        let checksums = ChecksumFiles::new(&build, &release_config, &tag)?;
+
+        let mut assets = vec![];
+        for name in ["tool.tar.gz", "tool.tar.gz.sha256"] {
+            let path = dir.path().join(name);
+            fs::write(&path, name)?;
+            assets.push(Asset::new(name, path));
+        }
+
+        let error = Release::new(1, "rvigo", "tool")
+            .upload_assets(assets, &tag, &checksums, None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("duplicates: tool.tar.gz.sha256"));
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
 pub mod pull_request_response;
+pub mod release_assets_response;
 pub mod release_response;
 pub mod sha_response;
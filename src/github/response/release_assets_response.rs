@@ -0,0 +1,18 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseAssetsResponse {
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAssetResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseAssetResponse {
+    pub name: String,
+    pub size: u64,
+}
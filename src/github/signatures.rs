@@ -0,0 +1,36 @@
+use super::asset::Asset;
+use crate::{build::Build, config::ReleaseConfig, signing::Signer};
+use anyhow::Result;
+use std::{fs, path::PathBuf};
+
+/// The signatures of the release assets, written to the dist directory
+pub struct Signatures {
+    signer: Signer,
+    dir: PathBuf,
+}
+
+impl Signatures {
+    /// Loads the signing key, when the release is signed
+    pub fn new(build: &Build, release_config: &ReleaseConfig) -> Result<Option<Self>> {
+        let Some(config) = &release_config.signing else {
+            return Ok(None);
+        };
+
+        Ok(Some(Signatures {
+            signer: Signer::load(config)?,
+            dir: build.dist_dir().to_owned(),
+        }))
+    }
+
+    /// The signature of the asset, named after it
+    pub fn sign(&self, asset: &Asset) -> Result<Asset> {
+        let file_name = format!("{}.{}", asset.name, self.signer.format().extension());
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(&file_name);
+
+        log::debug!("signing {}", asset.name);
+        self.signer.sign(&asset.path, &path)?;
+
+        Ok(Asset::new(file_name, path))
+    }
+}
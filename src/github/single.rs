@@ -1,54 +1,97 @@
 use crate::{
+    artifacts::{Artifact, Artifacts},
     brew::package::Package,
-    build::Build,
-    checksum::Checksum,
-    compression::compress_file,
+    build::{os::Os, Build},
     config::ReleaseConfig,
     cwd, git,
-    github::{check_binary, create_compressed_asset, get_release, SINGLE_TARGET_DIR},
+    github::{
+        archive_mtime, asset::Asset, check_binary, checksums::ChecksumFiles, create_binary_asset,
+        create_build_logs_asset, create_debug_asset, create_source_assets, get_release,
+        report_sizes, signatures::Signatures,
+    },
+    naming::NameContext,
 };
 use anyhow::{bail, Result};
-use std::path::PathBuf;
+use tokio::task;
 
 pub async fn release(build: &Build, release_config: &ReleaseConfig) -> Result<Vec<Package>> {
-    check_binary(&build.binary.to_owned(), None)?;
+    check_binary(build, None)?;
 
     let tag = git::get_current_tag(cwd!())?;
+    let mtime = archive_mtime(build, &tag)?;
+    let checksums = ChecksumFiles::new(build, release_config, &tag)?;
+    let signatures = Signatures::new(build, release_config)?;
 
-    let binary_name = format!(
-        "{}_{}.{}",
-        build.binary,
-        tag.name(),
-        release_config.archive.compression.extension()
-    );
-
-    log::debug!("compressing binary");
-    let compressed_file_path = compress_file(
-        &build.binary.to_owned(),
-        PathBuf::from(format!("{}/{}", SINGLE_TARGET_DIR, build.binary)),
-        &binary_name.to_owned(),
-        &release_config.archive.files,
-        &release_config.archive.compression,
-    )?;
+    let context = NameContext::for_host(
+        build.name(),
+        &tag,
+        release_config.archive.compression(&Os::host()),
+    )
+    .with_binary(build.binary_name());
+    let binary_name = release_config.archive.asset_name(&context)?;
+    let binaries = build.binaries(&Os::host());
 
     log::debug!("creating asset");
-    let mut asset = create_compressed_asset(
-        &binary_name,
-        compressed_file_path,
-        &release_config.archive.compression,
-    );
+    let archive = {
+        let (build, release_config, binaries, binary_name, context) = (
+            build.to_owned(),
+            release_config.to_owned(),
+            binaries.to_owned(),
+            binary_name.to_owned(),
+            context.to_owned(),
+        );
+
+        task::spawn_blocking(move || -> Result<(Asset, Option<Asset>)> {
+            let asset = create_binary_asset(
+                &binaries,
+                build.binaries_dir(None),
+                build.dist_dir(),
+                &binary_name,
+                &context,
+                &Os::host(),
+                &release_config,
+                mtime,
+            )?;
+            let debug_asset = create_debug_asset(
+                &build,
+                None,
+                &Os::host(),
+                &binary_name,
+                &release_config,
+                mtime,
+            )?;
+
+            Ok((asset, debug_asset))
+        })
+    };
+    let (asset, debug_asset) = archive.await??;
+    let asset_name = asset.name.to_owned();
+
+    let mut artifacts = Artifacts::new(&tag);
+    artifacts.push(Artifact::new(
+        &asset,
+        None,
+        &context,
+        binaries
+            .iter()
+            .map(|binary| build.binaries_dir(None).join(&binary.name)),
+    )?);
 
-    log::debug!("generating checksum");
-    let checksum = Checksum::try_from(&asset)?;
+    let mut assets = vec![asset];
+    assets.extend(debug_asset);
+    assets.extend(create_build_logs_asset(build, release_config, &tag, mtime)?);
+    assets.extend(create_source_assets(build, release_config, &tag).await?);
 
-    log::debug!("adding checksum to asset");
-    asset.add_checksum(checksum.value());
+    report_sizes(build, release_config, &mut artifacts, &tag).await?;
 
     log::debug!("getting/creating release");
     let release = get_release(release_config, &tag).await?;
 
     log::debug!("uploading asset");
-    let uploaded_assets = match release.upload_assets(vec![asset], &tag).await {
+    let uploaded_assets = match release
+        .upload_assets(assets, &tag, &checksums, signatures.as_ref())
+        .await
+    {
         Ok(uploaded_assets) => uploaded_assets,
         Err(e) => {
             log::error!("Failed to upload asset {:#?}", e);
@@ -58,6 +101,7 @@ pub async fn release(build: &Build, release_config: &ReleaseConfig) -> Result<Ve
 
     let packages: Vec<Package> = uploaded_assets
         .iter()
+        .filter(|asset| asset.name == asset_name)
         .map(|asset| {
             Package::new(
                 asset.name.to_owned(),
@@ -0,0 +1,107 @@
+/// Whether the SPDX license expression is satisfied by the allowed licenses.
+///
+/// `OR` needs one allowed side and `AND` both, an exception following `WITH` being allowed
+/// along with its license. The legacy `/` separator reads as `OR`, and an invalid expression
+/// is never allowed
+pub fn is_allowed(expression: &str, allow: &[String]) -> bool {
+    let spaced = expression
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .replace('/', " OR ");
+    let mut parser = Parser {
+        tokens: spaced.split_whitespace().collect(),
+        position: 0,
+        allow,
+    };
+
+    match parser.or() {
+        Some(allowed) => allowed && parser.position == parser.tokens.len(),
+        None => false,
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+    allow: &'a [String],
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Option<bool> {
+        let mut allowed = self.and()?;
+        while self.next_is("OR") {
+            allowed = self.and()? || allowed;
+        }
+        Some(allowed)
+    }
+
+    fn and(&mut self) -> Option<bool> {
+        let mut allowed = self.license()?;
+        while self.next_is("AND") {
+            allowed = self.license()? && allowed;
+        }
+        Some(allowed)
+    }
+
+    fn license(&mut self) -> Option<bool> {
+        let token = *self.tokens.get(self.position)?;
+        self.position += 1;
+
+        if token == "(" {
+            let allowed = self.or()?;
+            return self.next_is(")").then_some(allowed);
+        }
+        if matches!(token, ")" | "OR" | "AND" | "WITH") {
+            return None;
+        }
+
+        let mut allowed = self.is_listed(token);
+        if self.next_is("WITH") {
+            let exception = *self.tokens.get(self.position)?;
+            self.position += 1;
+            allowed = allowed || self.is_listed(&format!("{} WITH {}", token, exception));
+        }
+        Some(allowed)
+    }
+
+    fn next_is(&mut self, keyword: &str) -> bool {
+        let matches = self
+            .tokens
+            .get(self.position)
+            .is_some_and(|token| token.eq_ignore_ascii_case(keyword));
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn is_listed(&self, license: &str) -> bool {
+        self.allow
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(license))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow() -> Vec<String> {
+        vec!["MIT".to_owned(), "Apache-2.0".to_owned()]
+    }
+
+    #[test]
+    fn should_evaluate_license_expressions() {
+        assert!(is_allowed("MIT", &allow()));
+        assert!(is_allowed("MIT OR GPL-3.0", &allow()));
+        assert!(is_allowed("MIT/Apache-2.0", &allow()));
+        assert!(is_allowed("(MIT OR Apache-2.0) AND Apache-2.0", &allow()));
+        assert!(is_allowed("Apache-2.0 WITH LLVM-exception", &allow()));
+
+        assert!(!is_allowed("GPL-3.0", &allow()));
+        assert!(!is_allowed("MIT AND Unicode-DFS-2016", &allow()));
+        assert!(!is_allowed("(MIT OR Apache-2.0", &allow()));
+        assert!(!is_allowed("MIT OR", &allow()));
+        assert!(!is_allowed("", &allow()));
+    }
+}
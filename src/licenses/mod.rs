@@ -0,0 +1,371 @@
+mod expression;
+
+use crate::{
+    build::{arch::Arch, os::Os, Build},
+    bundle,
+    cargo::{
+        self,
+        arch_os_matrix::ArchOsMatrixEntry,
+        metadata::{self, Package},
+    },
+    config::{LicensesConfig, ReleaseConfig},
+    cwd,
+    naming::host_target,
+    universal::UNIVERSAL_TARGET,
+};
+use anyhow::{bail, Context, Result};
+use handlebars::Handlebars;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const THIRD_PARTY_LICENSES_FILE_NAME: &str = "THIRD_PARTY_LICENSES";
+const TEMPLATE_NAME: &str = "third_party_licenses";
+const LICENSES_DIR_NAME: &str = "licenses";
+
+/// A dependency shipped in the binaries, with the texts of its licenses
+#[derive(Debug, Clone, Serialize)]
+struct ThirdPartyCrate {
+    name: String,
+    version: String,
+    license: Option<String>,
+    repository: Option<String>,
+    license_texts: Vec<LicenseText>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LicenseText {
+    file: String,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Notice<'a> {
+    name: &'a str,
+    target: &'a str,
+    crates: Vec<&'a ThirdPartyCrate>,
+}
+
+/// The third party licenses file of a target, in the dist dir
+pub fn path(dist_dir: &Path, target: &str) -> PathBuf {
+    dist_dir
+        .join(LICENSES_DIR_NAME)
+        .join(target)
+        .join(THIRD_PARTY_LICENSES_FILE_NAME)
+}
+
+/// Writes the third party licenses of every released target, built or prebuilt, into the
+/// dist dir when configured
+pub async fn generate(build: &Build, release_config: &ReleaseConfig) -> Result<()> {
+    let Some(config) = &release_config.archive.third_party_licenses else {
+        return Ok(());
+    };
+    let hb = handlebars(config)?;
+    let mut violations = vec![];
+
+    let mut darwin_crates = BTreeMap::new();
+    for target in targets(build) {
+        // the host dependencies are resolved for every platform when rustc has no such target
+        let triple = match &target {
+            Some(target) => Some(target.to_string()),
+            None => Arch::host().map(|arch| ArchOsMatrixEntry::new(&arch, &Os::host()).to_string()),
+        };
+        log::info!(
+            "collecting the third party licenses for {}",
+            triple.as_deref().unwrap_or("every platform")
+        );
+
+        let crates = third_party_crates(triple.as_deref()).await?;
+        violations.extend(check_allowed(&crates, config));
+        if target
+            .as_ref()
+            .is_some_and(|target| target.os == Os::AppleDarwin)
+        {
+            darwin_crates.extend(crates.to_owned());
+        }
+
+        let triple = triple.unwrap_or_else(host_target);
+        write(
+            &hb,
+            build.name(),
+            &triple,
+            &crates,
+            &path(build.dist_dir(), &triple),
+        )?;
+    }
+
+    if build.universal {
+        write(
+            &hb,
+            build.name(),
+            UNIVERSAL_TARGET,
+            &darwin_crates,
+            &path(build.dist_dir(), UNIVERSAL_TARGET),
+        )?;
+    }
+
+    if !violations.is_empty() {
+        violations.sort();
+        violations.dedup();
+        bail!(anyhow::anyhow!(
+            "dependencies with a license outside the allow-list:\n{}",
+            violations.join("\n")
+        ));
+    }
+
+    Ok(())
+}
+
+/// The built targets, or the distinct targets of the prebuilt binaries
+fn targets(build: &Build) -> Vec<Option<ArchOsMatrixEntry>> {
+    let Some(prebuilt) = &build.prebuilt else {
+        return cargo::targets(build);
+    };
+
+    let mut targets: Vec<Option<ArchOsMatrixEntry>> = vec![];
+    for asset in prebuilt {
+        let (Some(arch), Some(os)) = (&asset.arch, &asset.os) else {
+            continue;
+        };
+        let target = ArchOsMatrixEntry::new(arch, os);
+        if !targets
+            .iter()
+            .flatten()
+            .any(|known| known.to_string() == target.to_string())
+        {
+            targets.push(Some(target));
+        }
+    }
+
+    targets
+}
+
+fn handlebars(config: &LicensesConfig) -> Result<Handlebars<'static>> {
+    let mut hb = Handlebars::new();
+    hb.register_escape_fn(handlebars::no_escape);
+
+    match &config.template {
+        Some(path) => hb
+            .register_template_file(TEMPLATE_NAME, path)
+            .with_context(|| format!("Cannot read the template {}", path.display()))?,
+        None => {
+            hb.register_template_string(TEMPLATE_NAME, include_str!("./third_party_licenses.hbs"))?
+        }
+    }
+
+    Ok(hb)
+}
+
+/// The dependencies of the crate, by name and version, built for the target
+async fn third_party_crates(target: Option<&str>) -> Result<BTreeMap<String, ThirdPartyCrate>> {
+    let metadata = metadata::dependency_metadata(target).await?;
+    let package = metadata
+        .package_at(cwd!())
+        .context("Cannot find the package in the cargo metadata")?;
+
+    let mut crates = BTreeMap::new();
+    for dependency in metadata.normal_dependencies(package) {
+        let license_texts = license_texts(dependency)?;
+        if license_texts.is_empty() {
+            log::warn!(
+                "no license file found for {} {}",
+                dependency.name,
+                dependency.version
+            );
+        }
+
+        crates.insert(
+            format!("{} {}", dependency.name, dependency.version),
+            ThirdPartyCrate {
+                name: dependency.name.to_owned(),
+                version: dependency.version.to_owned(),
+                license: dependency.license.to_owned(),
+                repository: dependency.repository.to_owned(),
+                license_texts,
+            },
+        );
+    }
+
+    Ok(crates)
+}
+
+/// The license files of the crate sources, from the registry or vendor directory
+fn license_texts(package: &Package) -> Result<Vec<LicenseText>> {
+    let Some(dir) = package.manifest_path.parent() else {
+        return Ok(vec![]);
+    };
+
+    let mut files = fs::read_dir(dir)
+        .with_context(|| format!("Cannot read {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|path| path.is_file() && bundle::is_license(path))
+        .collect::<Vec<_>>();
+    if let Some(license_file) = &package.license_file {
+        let path = dir.join(license_file);
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    files
+        .iter()
+        .map(|path| {
+            let text = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+            Ok(LicenseText {
+                file: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                text: String::from_utf8_lossy(&text).trim_end().to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// The crates whose license is not allowed, when an allow-list is configured
+fn check_allowed(
+    crates: &BTreeMap<String, ThirdPartyCrate>,
+    config: &LicensesConfig,
+) -> Vec<String> {
+    let Some(allow) = &config.allow else {
+        return vec![];
+    };
+
+    crates
+        .values()
+        .filter(|dependency| {
+            !dependency
+                .license
+                .as_deref()
+                .is_some_and(|license| expression::is_allowed(license, allow))
+        })
+        .map(|dependency| {
+            format!(
+                "{} {}: {}",
+                dependency.name,
+                dependency.version,
+                dependency
+                    .license
+                    .as_deref()
+                    .unwrap_or("no license expression")
+            )
+        })
+        .collect()
+}
+
+fn write(
+    hb: &Handlebars,
+    name: &str,
+    target: &str,
+    crates: &BTreeMap<String, ThirdPartyCrate>,
+    path: &Path,
+) -> Result<()> {
+    let notice = Notice {
+        name,
+        target,
+        crates: crates.values().collect(),
+    };
+    let rendered = hb.render(TEMPLATE_NAME, &notice)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, rendered).with_context(|| format!("Cannot write {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::prebuilt::PreBuiltAsset;
+    use tempdir::TempDir;
+
+    fn dependency(license: Option<&str>) -> ThirdPartyCrate {
+        ThirdPartyCrate {
+            name: "serde".to_owned(),
+            version: "1.0.0".to_owned(),
+            license: license.map(str::to_owned),
+            repository: Some("https://github.com/serde-rs/serde".to_owned()),
+            license_texts: vec![LicenseText {
+                file: "LICENSE-MIT".to_owned(),
+                text: "Permission is hereby granted, free of charge".to_owned(),
+            }],
+        }
+    }
+
+    #[test]
+    fn should_render_every_crate_with_its_license_texts() {
+        let dir = TempDir::new("licenses").unwrap();
+        let hb = handlebars(&LicensesConfig::default()).unwrap();
+        let crates = BTreeMap::from([("serde 1.0.0".to_owned(), dependency(Some("MIT")))]);
+
+        let path = path(dir.path(), "x86_64-unknown-linux-gnu");
+        write(&hb, "tool", "x86_64-unknown-linux-gnu", &crates, &path).unwrap();
+
+        assert!(path.ends_with("licenses/x86_64-unknown-linux-gnu/THIRD_PARTY_LICENSES"));
+        let notice = fs::read_to_string(&path).unwrap();
+        assert!(notice.contains("Third party licenses of tool for x86_64-unknown-linux-gnu"));
+        assert!(notice
+            .contains("serde 1.0.0\nLicense: MIT\nRepository: https://github.com/serde-rs/serde"));
+        assert!(notice.contains("LICENSE-MIT"));
+        assert!(notice.contains("Permission is hereby granted, free of charge"));
+    }
+
+    #[test]
+    fn should_collect_the_licenses_of_every_prebuilt_target() {
+        let prebuilt = |path: &str, arch: Arch, os: Os| PreBuiltAsset {
+            path: path.into(),
+            arch: Some(arch),
+            os: Some(os),
+        };
+        let build = Build {
+            prebuilt: Some(vec![
+                prebuilt("linux/tool", Arch::Amd64, Os::UnknownLinuxGnu),
+                prebuilt("linux/toold", Arch::Amd64, Os::UnknownLinuxGnu),
+                prebuilt("darwin/tool", Arch::Arm64, Os::AppleDarwin),
+            ]),
+            ..Default::default()
+        };
+
+        let targets: Vec<String> = targets(&build)
+            .into_iter()
+            .flatten()
+            .map(|target| target.to_string())
+            .collect();
+
+        assert_eq!(
+            targets,
+            vec!["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"]
+        );
+    }
+
+    #[test]
+    fn should_list_the_licenses_outside_the_allow_list() {
+        let crates = BTreeMap::from([
+            ("a".to_owned(), dependency(Some("MIT OR Apache-2.0"))),
+            ("b".to_owned(), dependency(Some("GPL-3.0"))),
+            ("c".to_owned(), dependency(None)),
+        ]);
+        let config = LicensesConfig {
+            allow: Some(vec!["MIT".to_owned()]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            check_allowed(&crates, &config),
+            vec![
+                "serde 1.0.0: GPL-3.0".to_owned(),
+                "serde 1.0.0: no license expression".to_owned()
+            ]
+        );
+        assert!(check_allowed(&crates, &LicensesConfig::default()).is_empty());
+    }
+}
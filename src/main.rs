@@ -1,24 +1,53 @@
+mod artifacts;
 mod brew;
 mod build;
+mod bundle;
 mod cargo;
 mod checksum;
+mod cli;
 mod compression;
 mod config;
+mod debuginfo;
 mod git;
 mod github;
 mod http;
+mod licenses;
 mod logger;
+mod naming;
+mod object_header;
+mod self_test;
+mod signing;
+mod universal;
 
 use anyhow::{Context, Result};
 use build::TargetType;
+use clap::Parser;
+use cli::{Cli, Command};
 use config::Config;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     logger::init()?;
+    let cli = Cli::parse();
 
+    match cli.command.unwrap_or_default() {
+        Command::Release => release(load_config().await?).await,
+        Command::VerifyReproducible => cargo::verify_reproducible(&load_config().await?.build)
+            .await
+            .context("Cannot verify the build reproducibility"),
+        Command::Verify(args) => {
+            signing::verify(&args.file, &args.public_key, args.signature.as_deref())
+                .context("Cannot verify the signature")
+        }
+    }
+}
+
+async fn load_config() -> Result<Config> {
     log::info!("Starting");
-    let config = Config::load().await.context("Cannot load config file")?;
+    Config::load().await.context("Cannot load config file")
+}
+
+async fn release(config: Config) -> Result<()> {
     let build_info = config.build;
     let release_info = config.release;
 
@@ -27,8 +56,18 @@ async fn main() -> Result<()> {
         cargo::build(&build_info)
             .await
             .context("Cannot build the project")?;
+
+        debuginfo::strip(&build_info)
+            .await
+            .context("Cannot strip the binaries")?;
+
+        universal::merge(&build_info).context("Cannot create the universal binaries")?;
     }
 
+    licenses::generate(&build_info, &release_info)
+        .await
+        .context("Cannot bundle the third party licenses")?;
+
     log::info!("Creating release");
     let packages = github::release(&build_info, &release_info)
         .await
@@ -36,7 +75,7 @@ async fn main() -> Result<()> {
 
     if let Some(brew) = config.brew {
         log::info!("Creating brew formula");
-        brew::publish(brew, packages)
+        brew::publish(brew, packages, &build_info.binary, &release_info.archive)
             .await
             .context("Cannot publish the brew formula")?;
     }
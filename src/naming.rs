@@ -0,0 +1,242 @@
+use crate::{
+    build::{arch::Arch, os::Os},
+    cargo::arch_os_matrix::ArchOsMatrixEntry,
+    compression::Compression,
+    git::tag::Tag,
+    universal::UNIVERSAL_TARGET,
+};
+use anyhow::{bail, Context, Result};
+use handlebars::Handlebars;
+use serde::Serialize;
+use std::{collections::HashSet, env, fmt::Display};
+
+const UNIVERSAL_ARCH: &str = "universal";
+
+/// The values the archive templates can refer to
+#[derive(Debug, Clone, Serialize)]
+pub struct NameContext {
+    pub name: String,
+    /// The name of the first binary inside the archive, renamed or not
+    pub binary: String,
+    /// The tag without its leading `v`
+    pub version: String,
+    pub tag: String,
+    pub os: String,
+    pub arch: String,
+    /// The rustc target triple
+    pub target: String,
+    /// The archive format, such as `tar.gz`, or `binary`
+    pub format: String,
+}
+
+impl NameContext {
+    pub fn new(
+        name: impl Into<String>,
+        tag: &Tag,
+        arch: impl Display,
+        os: &Os,
+        target: impl Into<String>,
+        compression: &Compression,
+    ) -> Self {
+        let name = name.into();
+        NameContext {
+            binary: name.to_owned(),
+            name,
+            version: tag.strip_v_prefix().to_owned(),
+            tag: tag.name().to_owned(),
+            os: os.to_string(),
+            arch: arch.to_string(),
+            target: target.into(),
+            format: compression.extension().unwrap_or("binary").to_owned(),
+        }
+    }
+
+    pub fn for_target(
+        name: impl Into<String>,
+        tag: &Tag,
+        target: &ArchOsMatrixEntry,
+        compression: &Compression,
+    ) -> Self {
+        Self::new(
+            name,
+            tag,
+            &target.arch,
+            &target.os,
+            target.to_string(),
+            compression,
+        )
+    }
+
+    /// The context of the host, the target of single target builds
+    pub fn for_host(name: impl Into<String>, tag: &Tag, compression: &Compression) -> Self {
+        let os = Os::host();
+        match Arch::host() {
+            Some(arch) => {
+                Self::for_target(name, tag, &ArchOsMatrixEntry::new(&arch, &os), compression)
+            }
+            None => Self::new(
+                name,
+                tag,
+                env::consts::ARCH,
+                &os,
+                format!("{}-{}", env::consts::ARCH, os.target()),
+                compression,
+            ),
+        }
+    }
+
+    /// The context of the universal darwin binaries, made for every darwin arch
+    pub fn for_universal(name: impl Into<String>, tag: &Tag, compression: &Compression) -> Self {
+        Self::new(
+            name,
+            tag,
+            UNIVERSAL_ARCH,
+            &Os::AppleDarwin,
+            UNIVERSAL_TARGET,
+            compression,
+        )
+    }
+
+    /// The same values, the binary being archived under another name
+    pub fn with_binary(self, binary: impl Into<String>) -> Self {
+        NameContext {
+            binary: binary.into(),
+            ..self
+        }
+    }
+
+    /// The same values for another release
+    pub fn with_tag(&self, tag: &Tag) -> Self {
+        NameContext {
+            version: tag.strip_v_prefix().to_owned(),
+            tag: tag.name().to_owned(),
+            ..self.to_owned()
+        }
+    }
+
+    /// Whether the host can run the binaries of the target
+    pub fn runs_on_host(&self) -> bool {
+        self.target == host_target()
+            || (self.target == UNIVERSAL_TARGET && Os::host() == Os::AppleDarwin)
+    }
+
+    /// Renders the template, failing on unknown values or an empty result
+    pub fn render(&self, template: &str) -> Result<String> {
+        render(template, self)
+    }
+}
+
+/// The values the templates of the release-wide files can refer to
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseContext {
+    pub name: String,
+    /// The tag without its leading `v`
+    pub version: String,
+    pub tag: String,
+}
+
+impl ReleaseContext {
+    pub fn new(name: impl Into<String>, tag: &Tag) -> Self {
+        ReleaseContext {
+            name: name.into(),
+            version: tag.strip_v_prefix().to_owned(),
+            tag: tag.name().to_owned(),
+        }
+    }
+}
+
+/// Renders the template with the values, failing on unknown values or an empty result
+pub fn render(template: &str, values: &impl Serialize) -> Result<String> {
+    let mut hb = Handlebars::new();
+    hb.set_strict_mode(true);
+    hb.register_escape_fn(handlebars::no_escape);
+
+    let rendered = hb
+        .render_template(template, values)
+        .with_context(|| format!("Cannot render {}", template))?;
+    if rendered.trim().is_empty() {
+        bail!(anyhow::anyhow!("{} renders an empty name", template));
+    }
+
+    Ok(rendered)
+}
+
+/// The rustc target triple of the host
+pub fn host_target() -> String {
+    let os = Os::host();
+    match Arch::host() {
+        Some(arch) => ArchOsMatrixEntry::new(&arch, &os).to_string(),
+        None => format!("{}-{}", env::consts::ARCH, os.target()),
+    }
+}
+
+/// Fails when two assets of the release would have the same name
+pub fn check_unique(names: impl IntoIterator<Item = impl AsRef<str>>) -> Result<()> {
+    let mut seen = HashSet::new();
+    let duplicates: Vec<String> = names
+        .into_iter()
+        .map(|name| name.as_ref().to_owned())
+        .filter(|name| !seen.insert(name.to_owned()))
+        .collect();
+
+    if !duplicates.is_empty() {
+        bail!(anyhow::anyhow!(
+            "asset names must be unique in a release, found duplicates: {}. \
+             Check that archive.name_template tells the targets apart",
+            duplicates.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_render_the_context() {
+        let target = ArchOsMatrixEntry::new(&Arch::Amd64, &Os::UnknownLinuxGnu);
+        let context =
+            NameContext::for_target("tool", &Tag::new("v1.0.0"), &target, &Compression::TarXz);
+
+        assert_eq!(
+            context
+                .render("{{ name }}-{{ tag }}-{{ arch }}-{{ os }}")
+                .unwrap(),
+            "tool-v1.0.0-x86_64-linux"
+        );
+        assert_eq!(
+            context
+                .render("{{ name }}_{{ version }}_{{ target }}.{{ format }}")
+                .unwrap(),
+            "tool_1.0.0_x86_64-unknown-linux-gnu.tar.xz"
+        );
+        assert!(context.render("{{ name }}-{{ abi }}").is_err());
+        assert!(context.render(" ").is_err());
+    }
+
+    #[test]
+    fn should_render_the_names_without_escaping() {
+        let target = ArchOsMatrixEntry::new(&Arch::Amd64, &Os::UnknownLinuxGnu);
+        let context =
+            NameContext::for_target("r&d", &Tag::new("v1.0.0"), &target, &Compression::TarGz);
+
+        assert_eq!(
+            context
+                .render("{{ name }}-'{{ tag }}'=\"<{{ arch }}>\"")
+                .unwrap(),
+            "r&d-'v1.0.0'=\"<x86_64>\""
+        );
+    }
+
+    #[test]
+    fn should_reject_duplicate_names() {
+        assert!(check_unique(["tool-x86_64.tar.gz", "tool-aarch64.tar.gz"]).is_ok());
+
+        let error = check_unique(["tool.tar.gz", "tool.zip", "tool.tar.gz"])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("duplicates: tool.tar.gz."));
+    }
+}
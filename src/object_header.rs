@@ -0,0 +1,447 @@
+use crate::build::{arch::Arch, os::Os};
+use anyhow::{bail, Context, Result};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+pub(crate) const MACHO_MAGIC_32: [u8; 4] = [0xce, 0xfa, 0xed, 0xfe];
+pub(crate) const MACHO_MAGIC_64: [u8; 4] = [0xcf, 0xfa, 0xed, 0xfe];
+pub(crate) const MACHO_FAT_MAGIC: [u8; 4] = [0xca, 0xfe, 0xba, 0xbe];
+const MACHO_FAT_MAGIC_64: [u8; 4] = [0xca, 0xfe, 0xba, 0xbf];
+const MACHO_FAT_ARCH_SIZE: usize = 20;
+const MACHO_FAT_ARCH_64_SIZE: usize = 32;
+const PE_MAGIC: [u8; 2] = [b'M', b'Z'];
+const PE_SIGNATURE: [u8; 4] = [b'P', b'E', 0, 0];
+
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_BIG_ENDIAN: u8 = 2;
+const ELF_MACHINE_ARM: u16 = 40;
+const ELF_MACHINE_X86_64: u16 = 62;
+const ELF_MACHINE_AARCH64: u16 = 183;
+const ELF_PROGRAM_INTERPRETER: u32 = 3;
+
+pub(crate) const MACHO_CPU_X86_64: u32 = 0x0100_0007;
+const MACHO_CPU_ARM: u32 = 12;
+pub(crate) const MACHO_CPU_ARM64: u32 = 0x0100_000c;
+
+const PE_MACHINE_AMD64: u16 = 0x8664;
+const PE_MACHINE_ARM: u16 = 0x01c4;
+const PE_MACHINE_ARM64: u16 = 0xaa64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Format {
+    Elf,
+    MachO,
+    Pe,
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Elf => write!(f, "ELF"),
+            Format::MachO => write!(f, "Mach-O"),
+            Format::Pe => write!(f, "PE"),
+        }
+    }
+}
+
+/// The parts of an executable header that tell which target it was built for
+#[derive(Debug, Clone)]
+pub struct ObjectHeader {
+    pub format: Format,
+    /// Every architecture in the file, more than one for a Mach-O universal binary
+    pub archs: Vec<Arch>,
+    /// Whether the ELF binary asks for a dynamic loader
+    pub dynamically_linked: bool,
+}
+
+impl ObjectHeader {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file =
+            File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)
+            .with_context(|| format!("{} is not an executable", path.display()))?;
+
+        match magic {
+            ELF_MAGIC => read_elf(&mut file),
+            MACHO_MAGIC_32 | MACHO_MAGIC_64 => read_macho(&mut file),
+            MACHO_FAT_MAGIC => read_fat_macho(&mut file, MACHO_FAT_ARCH_SIZE),
+            MACHO_FAT_MAGIC_64 => read_fat_macho(&mut file, MACHO_FAT_ARCH_64_SIZE),
+            _ if magic[..2] == PE_MAGIC => read_pe(&mut file),
+            _ => bail!(anyhow::anyhow!(
+                "{} is not an ELF, Mach-O or PE executable",
+                path.display()
+            )),
+        }
+    }
+
+    /// Fails when the header does not match the expected arch and os
+    pub fn verify(&self, arch: &Arch, os: &Os) -> Result<()> {
+        let expected_format = match os {
+            Os::AppleDarwin => Format::MachO,
+            Os::UnknownLinuxGnu | Os::UnknownLinuxMusl => Format::Elf,
+            Os::PcWindowsMsvc => Format::Pe,
+        };
+
+        if self.format != expected_format {
+            bail!(anyhow::anyhow!(
+                "expected a {} binary for {}, found {}",
+                expected_format,
+                os,
+                self.format
+            ));
+        }
+
+        if !self.archs.contains(arch) {
+            bail!(anyhow::anyhow!(
+                "expected a {} binary, found {}",
+                arch,
+                self.archs
+                    .iter()
+                    .map(|arch| arch.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the header of a binary and checks it against the target
+pub fn verify_binary(
+    path: impl AsRef<Path>,
+    arch: &Arch,
+    os: &Os,
+    static_musl: bool,
+) -> Result<()> {
+    let path = path.as_ref();
+    let header = ObjectHeader::read(path)?;
+    log::debug!("{} header: {:?}", path.display(), header);
+
+    header
+        .verify(arch, os)
+        .with_context(|| format!("{} does not match its target", path.display()))?;
+
+    if static_musl && *os == Os::UnknownLinuxMusl && header.dynamically_linked {
+        bail!(anyhow::anyhow!(
+            "{} is a musl binary but it is dynamically linked",
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+fn read_elf(file: &mut File) -> Result<ObjectHeader> {
+    let mut ident = [0u8; 64];
+    file.rewind()?;
+    file.read_exact(&mut ident)
+        .context("Truncated ELF header")?;
+
+    let is_64 = ident[4] == ELF_CLASS_64;
+    let endian = Endian::from_elf(ident[5]);
+
+    let machine = endian.u16(&ident[18..20]);
+    let arch = match machine {
+        ELF_MACHINE_X86_64 => Some(Arch::Amd64),
+        ELF_MACHINE_ARM => Some(Arch::Arm),
+        ELF_MACHINE_AARCH64 => Some(Arch::Arm64),
+        _ => None,
+    };
+
+    let (program_headers_offset, entry_size, entries) = if is_64 {
+        (
+            endian.u64(&ident[32..40]),
+            endian.u16(&ident[54..56]),
+            endian.u16(&ident[56..58]),
+        )
+    } else {
+        (
+            u64::from(endian.u32(&ident[28..32])),
+            endian.u16(&ident[42..44]),
+            endian.u16(&ident[44..46]),
+        )
+    };
+
+    let program_headers_size = u64::from(entry_size) * u64::from(entries);
+    if program_headers_offset.saturating_add(program_headers_size) > file.metadata()?.len() {
+        bail!(anyhow::anyhow!("Truncated ELF program headers"));
+    }
+
+    let mut program_headers = vec![0u8; program_headers_size as usize];
+    file.seek(SeekFrom::Start(program_headers_offset))?;
+    file.read_exact(&mut program_headers)
+        .context("Truncated ELF program headers")?;
+
+    let dynamically_linked = program_headers
+        .chunks_exact(usize::from(entry_size).max(4))
+        .any(|header| endian.u32(&header[0..4]) == ELF_PROGRAM_INTERPRETER);
+
+    Ok(ObjectHeader {
+        format: Format::Elf,
+        archs: arch.into_iter().collect(),
+        dynamically_linked,
+    })
+}
+
+fn read_macho(file: &mut File) -> Result<ObjectHeader> {
+    let mut cpu_type = [0u8; 4];
+    file.read_exact(&mut cpu_type)
+        .context("Truncated Mach-O header")?;
+
+    Ok(ObjectHeader {
+        format: Format::MachO,
+        archs: macho_arch(Endian::Little.u32(&cpu_type))
+            .into_iter()
+            .collect(),
+        dynamically_linked: false,
+    })
+}
+
+/// Reads the cpu types of a universal binary, whose fat_arch entries are 20 bytes long,
+/// or 32 bytes with 64-bit offsets
+fn read_fat_macho(file: &mut File, fat_arch_size: usize) -> Result<ObjectHeader> {
+    let mut count = [0u8; 4];
+    file.read_exact(&mut count)
+        .context("Truncated Mach-O universal header")?;
+
+    let mut archs = vec![];
+    for _ in 0..Endian::Big.u32(&count) {
+        let mut fat_arch = vec![0u8; fat_arch_size];
+        file.read_exact(&mut fat_arch)
+            .context("Truncated Mach-O universal header")?;
+        archs.extend(macho_arch(Endian::Big.u32(&fat_arch[0..4])));
+    }
+
+    Ok(ObjectHeader {
+        format: Format::MachO,
+        archs,
+        dynamically_linked: false,
+    })
+}
+
+fn read_pe(file: &mut File) -> Result<ObjectHeader> {
+    let mut dos_header = [0u8; 64];
+    file.rewind()?;
+    file.read_exact(&mut dos_header)
+        .context("Truncated DOS header")?;
+
+    let pe_offset = Endian::Little.u32(&dos_header[60..64]);
+    let mut pe_header = [0u8; 6];
+    file.seek(SeekFrom::Start(u64::from(pe_offset)))?;
+    file.read_exact(&mut pe_header)
+        .context("Truncated PE header")?;
+
+    if pe_header[0..4] != PE_SIGNATURE {
+        bail!(anyhow::anyhow!("Invalid PE signature"));
+    }
+
+    let arch = match Endian::Little.u16(&pe_header[4..6]) {
+        PE_MACHINE_AMD64 => Some(Arch::Amd64),
+        PE_MACHINE_ARM => Some(Arch::Arm),
+        PE_MACHINE_ARM64 => Some(Arch::Arm64),
+        _ => None,
+    };
+
+    Ok(ObjectHeader {
+        format: Format::Pe,
+        archs: arch.into_iter().collect(),
+        dynamically_linked: false,
+    })
+}
+
+fn macho_arch(cpu_type: u32) -> Option<Arch> {
+    match cpu_type {
+        MACHO_CPU_X86_64 => Some(Arch::Amd64),
+        MACHO_CPU_ARM => Some(Arch::Arm),
+        MACHO_CPU_ARM64 => Some(Arch::Arm64),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn from_elf(data: u8) -> Self {
+        if data == ELF_DATA_BIG_ENDIAN {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    fn u16(self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u64(self, bytes: &[u8]) -> u64 {
+        let mut buffer = [0u8; 8];
+        buffer.copy_from_slice(&bytes[0..8]);
+        match self {
+            Endian::Little => u64::from_le_bytes(buffer),
+            Endian::Big => u64::from_be_bytes(buffer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn elf(machine: u16, interpreter: bool) -> Vec<u8> {
+        let mut data = vec![0u8; 64 + 56];
+        data[0..4].copy_from_slice(&ELF_MAGIC);
+        data[4] = ELF_CLASS_64;
+        data[5] = 1;
+        data[18..20].copy_from_slice(&machine.to_le_bytes());
+        data[32..40].copy_from_slice(&64u64.to_le_bytes());
+        data[54..56].copy_from_slice(&56u16.to_le_bytes());
+        data[56..58].copy_from_slice(&1u16.to_le_bytes());
+        let program_type: u32 = if interpreter { 3 } else { 1 };
+        data[64..68].copy_from_slice(&program_type.to_le_bytes());
+        data
+    }
+
+    fn write(dir: &TempDir, name: &str, data: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn should_read_the_elf_machine() -> Result<()> {
+        let dir = TempDir::new("object_header")?;
+        let path = write(&dir, "tool", &elf(ELF_MACHINE_AARCH64, true));
+
+        let header = ObjectHeader::read(&path)?;
+
+        assert_eq!(header.format, Format::Elf);
+        assert_eq!(header.archs, vec![Arch::Arm64]);
+        assert!(header.dynamically_linked);
+        assert!(header.verify(&Arch::Arm64, &Os::UnknownLinuxGnu).is_ok());
+        assert!(header.verify(&Arch::Amd64, &Os::UnknownLinuxGnu).is_err());
+        assert!(header.verify(&Arch::Arm64, &Os::AppleDarwin).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_dynamically_linked_musl_binaries() -> Result<()> {
+        let dir = TempDir::new("object_header")?;
+        let dynamic = write(&dir, "dynamic", &elf(ELF_MACHINE_X86_64, true));
+        let fully_static = write(&dir, "static", &elf(ELF_MACHINE_X86_64, false));
+
+        assert!(verify_binary(&dynamic, &Arch::Amd64, &Os::UnknownLinuxMusl, true).is_err());
+        assert!(verify_binary(&dynamic, &Arch::Amd64, &Os::UnknownLinuxMusl, false).is_ok());
+        assert!(verify_binary(&fully_static, &Arch::Amd64, &Os::UnknownLinuxMusl, true).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_the_macho_cpu_types() -> Result<()> {
+        let dir = TempDir::new("object_header")?;
+
+        let mut thin = MACHO_MAGIC_64.to_vec();
+        thin.extend(MACHO_CPU_ARM64.to_le_bytes());
+        let thin = write(&dir, "thin", &thin);
+
+        let mut fat = MACHO_FAT_MAGIC.to_vec();
+        fat.extend(2u32.to_be_bytes());
+        for cpu_type in [MACHO_CPU_X86_64, MACHO_CPU_ARM64] {
+            fat.extend(cpu_type.to_be_bytes());
+            fat.extend([0u8; 16]);
+        }
+        let fat = write(&dir, "fat", &fat);
+
+        let mut fat_64 = MACHO_FAT_MAGIC_64.to_vec();
+        fat_64.extend(2u32.to_be_bytes());
+        for cpu_type in [MACHO_CPU_ARM64, MACHO_CPU_X86_64] {
+            fat_64.extend(cpu_type.to_be_bytes());
+            fat_64.extend([0u8; 28]);
+        }
+        let fat_64 = write(&dir, "fat_64", &fat_64);
+
+        assert_eq!(ObjectHeader::read(thin)?.archs, vec![Arch::Arm64]);
+        assert_eq!(
+            ObjectHeader::read(fat)?.archs,
+            vec![Arch::Amd64, Arch::Arm64]
+        );
+        assert_eq!(
+            ObjectHeader::read(fat_64)?.archs,
+            vec![Arch::Arm64, Arch::Amd64]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_the_pe_machine() -> Result<()> {
+        let dir = TempDir::new("object_header")?;
+
+        let mut pe = vec![0u8; 70];
+        pe[0..2].copy_from_slice(&PE_MAGIC);
+        pe[60..64].copy_from_slice(&64u32.to_le_bytes());
+        pe[64..68].copy_from_slice(&PE_SIGNATURE);
+        pe[68..70].copy_from_slice(&PE_MACHINE_AMD64.to_le_bytes());
+        let path = write(&dir, "tool.exe", &pe);
+
+        let header = ObjectHeader::read(path)?;
+
+        assert_eq!(header.format, Format::Pe);
+        assert_eq!(header.archs, vec![Arch::Amd64]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_program_headers_past_the_end_of_the_file() -> Result<()> {
+        let dir = TempDir::new("object_header")?;
+        let mut data = elf(ELF_MACHINE_X86_64, false);
+        data[54..56].copy_from_slice(&u16::MAX.to_le_bytes());
+        data[56..58].copy_from_slice(&u16::MAX.to_le_bytes());
+        let path = write(&dir, "tool", &data);
+
+        let error = ObjectHeader::read(path).unwrap_err();
+
+        assert_eq!(error.to_string(), "Truncated ELF program headers");
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_unknown_files() -> Result<()> {
+        let dir = TempDir::new("object_header")?;
+        let path = write(&dir, "README", b"hello world");
+
+        assert!(ObjectHeader::read(path).is_err());
+
+        Ok(())
+    }
+}
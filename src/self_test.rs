@@ -0,0 +1,315 @@
+use crate::{
+    build::os::Os,
+    compression::{self, Compression, Contents},
+    config::SmokeTest,
+    naming::NameContext,
+};
+use anyhow::{bail, Context, Result};
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+/// Re-opens the archive before upload, checking it holds the expected entries with runnable
+/// binaries
+pub fn check(path: impl AsRef<Path>, compression: &Compression, expected: &Contents) -> Result<()> {
+    let path = path.as_ref();
+    log::info!("checking the archive {}", path.display());
+    let entries = compression::list_entries(path, compression)?;
+    let files: BTreeSet<String> = entries
+        .iter()
+        .filter(|entry| !entry.dir)
+        .map(|entry| entry.name.to_owned())
+        .collect();
+    let dirs: BTreeSet<String> = entries
+        .iter()
+        .filter(|entry| entry.dir)
+        .map(|entry| entry.name.to_owned())
+        .collect();
+
+    let missing: Vec<&String> = expected
+        .files
+        .difference(&files)
+        .chain(expected.dirs.difference(&dirs))
+        .collect();
+    let unexpected: Vec<&String> = files.difference(&expected.files).collect();
+    if !missing.is_empty() || !unexpected.is_empty() {
+        bail!(anyhow::anyhow!(
+            "{} does not hold the expected entries, missing: [{}], unexpected: [{}]",
+            path.display(),
+            missing
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            unexpected
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    for binary in &expected.binaries {
+        let Some(entry) = entries.iter().find(|entry| entry.name == *binary) else {
+            bail!(anyhow::anyhow!(
+                "{} is missing from {}",
+                binary,
+                path.display()
+            ));
+        };
+        if entry.mode & 0o111 == 0 {
+            bail!(anyhow::anyhow!(
+                "{} is not executable in {}, found mode {:o}",
+                binary,
+                path.display(),
+                entry.mode
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the smoke test against the extracted archive, when the host can run the target
+pub fn smoke_test(
+    path: impl AsRef<Path>,
+    compression: &Compression,
+    directory: Option<&str>,
+    context: &NameContext,
+    smoke_test: Option<&SmokeTest>,
+) -> Result<()> {
+    match smoke_test {
+        Some(smoke_test) if context.runs_on_host() => {
+            run_smoke_test(path.as_ref(), compression, directory, context, smoke_test)
+        }
+        Some(_) => {
+            log::debug!("skipping the smoke test of {}", context.target);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Extracts the archive into a temporary directory and runs the command from there
+fn run_smoke_test(
+    path: &Path,
+    compression: &Compression,
+    directory: Option<&str>,
+    context: &NameContext,
+    smoke_test: &SmokeTest,
+) -> Result<()> {
+    let dir = env::temp_dir().join(format!(
+        "rustreleaser-smoke-test-{}-{}",
+        process::id(),
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+
+    let result = compression::extract(path, compression, &dir).and_then(|_| {
+        run_command(
+            &dir.join(directory.unwrap_or_default()),
+            context,
+            smoke_test,
+        )
+    });
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        log::warn!("cannot remove {}: {}", dir.display(), e);
+    }
+
+    result.with_context(|| format!("The smoke test of {} failed", path.display()))
+}
+
+fn run_command(dir: &Path, context: &NameContext, smoke_test: &SmokeTest) -> Result<()> {
+    let command = context.render(&smoke_test.command)?;
+    let expect = context.render(&smoke_test.expect)?;
+    let mut words = command.split_whitespace();
+    let Some(program) = words.next() else {
+        bail!(anyhow::anyhow!("the smoke test command is empty"));
+    };
+
+    let program = executable(dir.join(program));
+    log::info!("running the smoke test {}", command);
+    let output = Command::new(&program)
+        .args(words)
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Cannot run {}", program.display()))?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    if !output.status.success() {
+        bail!(anyhow::anyhow!(
+            "{} exited with {}: {}",
+            command,
+            output.status,
+            text.trim()
+        ));
+    }
+    if !text.contains(&expect) {
+        bail!(anyhow::anyhow!(
+            "the output of {} does not contain {}: {}",
+            command,
+            expect,
+            text.trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// The program, with the executable extension of the host when it is left out
+fn executable(program: PathBuf) -> PathBuf {
+    match Os::host().executable_extension() {
+        Some(extension) if !program.exists() => program.with_extension(extension),
+        _ => program,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        build::{binary::Binary, Build},
+        compression::{compress_file, Layout},
+        git::tag::Tag,
+    };
+    use tempdir::TempDir;
+
+    #[cfg(unix)]
+    fn archive(dir: &Path, script: &str, mode: u32, layout: &Layout) -> Result<PathBuf> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let binary = dir.join("tool");
+        fs::write(&binary, script)?;
+        fs::set_permissions(&binary, fs::Permissions::from_mode(mode))?;
+
+        let name = "tool-v1.0.0";
+        let (path, _) = compress_file(
+            &[Binary::new("tool")],
+            dir,
+            dir,
+            name,
+            layout,
+            &Compression::TarGz,
+            None,
+            None,
+        )?;
+
+        Ok(path)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_run_the_smoke_test_from_the_extracted_archive() -> Result<()> {
+        let dir = TempDir::new("self_test")?;
+        let layout = Layout {
+            directory: Some("tool-v1.0.0".to_owned()),
+            ..Default::default()
+        };
+        let path = archive(
+            dir.path(),
+            "#!/bin/sh\necho \"tool 1.0.0\"\n",
+            0o755,
+            &layout,
+        )?;
+        let expected = Contents::new(&[Binary::new("tool")], layout.directory.as_deref());
+        let context = NameContext::for_host("tool", &Tag::new("v1.0.0"), &Compression::TarGz);
+
+        check(&path, &Compression::TarGz, &expected)?;
+        smoke_test(
+            &path,
+            &Compression::TarGz,
+            layout.directory.as_deref(),
+            &context,
+            Some(&SmokeTest::default()),
+        )?;
+
+        let failing = SmokeTest {
+            expect: "2.0.0".to_owned(),
+            ..Default::default()
+        };
+        let error = smoke_test(
+            &path,
+            &Compression::TarGz,
+            layout.directory.as_deref(),
+            &context,
+            Some(&failing),
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("does not contain 2.0.0"));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_run_the_default_smoke_test_with_the_renamed_binary() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("self_test")?;
+        let build = Build {
+            binary: vec![Binary {
+                name: "tool".to_owned(),
+                rename: Some("tl".to_owned()),
+            }],
+            ..Default::default()
+        };
+        fs::write(dir.path().join("tool"), "#!/bin/sh\necho \"tl 1.0.0\"\n")?;
+        fs::set_permissions(dir.path().join("tool"), fs::Permissions::from_mode(0o755))?;
+        let (path, _) = compress_file(
+            &build.binary,
+            dir.path(),
+            dir.path(),
+            "tool-v1.0.0",
+            &Layout::default(),
+            &Compression::TarGz,
+            None,
+            None,
+        )?;
+        let context = NameContext::for_host(build.name(), &Tag::new("v1.0.0"), &Compression::TarGz)
+            .with_binary(build.binary_name());
+
+        check(
+            &path,
+            &Compression::TarGz,
+            &Contents::new(&build.binary, None),
+        )?;
+        smoke_test(
+            &path,
+            &Compression::TarGz,
+            None,
+            &context,
+            Some(&SmokeTest::default()),
+        )?;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_reject_archives_with_unexpected_entries_or_binaries_not_executable() -> Result<()> {
+        let dir = TempDir::new("self_test")?;
+        let layout = Layout::default();
+        let path = archive(dir.path(), "binary", 0o644, &layout)?;
+        let mut expected = Contents::new(&[Binary::new("tool")], None);
+
+        let error = check(&path, &Compression::TarGz, &expected).unwrap_err();
+        assert!(error.to_string().contains("tool is not executable"));
+
+        expected.push_file(dir.path().join("THIRD_PARTY_LICENSES"));
+        let error = check(&path, &Compression::TarGz, &expected).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("missing: [THIRD_PARTY_LICENSES]"));
+
+        Ok(())
+    }
+}
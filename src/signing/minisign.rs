@@ -0,0 +1,345 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use blake2::{digest::consts::U32, Blake2b, Blake2b512, Digest};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::io::{self, Read};
+
+const SIGNATURE_ALGORITHM: &[u8] = b"Ed";
+/// Signs the BLAKE2b-512 hash of the file rather than its content
+const PREHASHED_SIGNATURE_ALGORITHM: &[u8] = b"ED";
+const KDF_ALGORITHM: &[u8] = b"Sc";
+const NO_KDF_ALGORITHM: &[u8] = &[0, 0];
+const CHECKSUM_ALGORITHM: &[u8] = b"B2";
+
+const SECRET_KEY_LEN: usize = 158;
+const PUBLIC_KEY_LEN: usize = 42;
+const SIGNATURE_LEN: usize = 74;
+const KEY_ID_LEN: usize = 8;
+
+const UNTRUSTED_COMMENT_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_COMMENT_PREFIX: &str = "trusted comment: ";
+
+/// The scrypt parameters libsodium picks from the limits stored in the key
+const MIN_OPSLIMIT: u64 = 32768;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_CHECK_LEN: usize = 32;
+
+type Blake2b256 = Blake2b<U32>;
+
+/// A minisign secret key, decrypted
+pub struct SecretKey {
+    key_id: [u8; KEY_ID_LEN],
+    signing_key: SigningKey,
+}
+
+impl SecretKey {
+    /// Reads the key file content, or only its base64 line, decrypting it with the password
+    pub fn decode(encoded: &str, password: Option<&str>) -> Result<Self> {
+        let bytes = decode_base64_line(encoded).context("Cannot read the minisign secret key")?;
+        if bytes.len() != SECRET_KEY_LEN
+            || &bytes[0..2] != SIGNATURE_ALGORITHM
+            || &bytes[4..6] != CHECKSUM_ALGORITHM
+        {
+            bail!(anyhow::anyhow!("not a minisign secret key"));
+        }
+
+        let mut keynum = bytes[54..].to_vec();
+        match &bytes[2..4] {
+            KDF_ALGORITHM => {
+                let Some(password) = password else {
+                    bail!(anyhow::anyhow!(
+                        "the minisign secret key is encrypted, its password is missing"
+                    ));
+                };
+                let opslimit = u64::from_le_bytes(bytes[38..46].try_into()?);
+                let memlimit = u64::from_le_bytes(bytes[46..54].try_into()?);
+                let stream = kdf_stream(password, &bytes[6..38], opslimit, memlimit, keynum.len())?;
+                keynum
+                    .iter_mut()
+                    .zip(stream)
+                    .for_each(|(byte, key)| *byte ^= key);
+            }
+            NO_KDF_ALGORITHM => {}
+            _ => bail!(anyhow::anyhow!(
+                "unsupported key derivation in the minisign secret key"
+            )),
+        }
+
+        let key_id: [u8; KEY_ID_LEN] = keynum[..8].try_into()?;
+        let keypair: [u8; 64] = keynum[8..72].try_into()?;
+        if key_checksum(&key_id, &keypair).as_slice() != &keynum[72..] {
+            bail!(anyhow::anyhow!(
+                "cannot decrypt the minisign secret key, check its password"
+            ));
+        }
+        let signing_key =
+            SigningKey::from_keypair_bytes(&keypair).context("Invalid minisign secret key")?;
+
+        Ok(SecretKey {
+            key_id,
+            signing_key,
+        })
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            key_id: self.key_id,
+            verifying_key: self.signing_key.verifying_key(),
+        }
+    }
+
+    /// The `.minisig` content of the data, signing its hash along with the trusted comment
+    pub fn sign(&self, data: impl Read, trusted_comment: &str) -> Result<String> {
+        let hash = prehash(data)?;
+        let signature = self.signing_key.sign(&hash).to_bytes();
+        let global_signature = self
+            .signing_key
+            .sign(&[&signature[..], trusted_comment.as_bytes()].concat());
+
+        Ok(format!(
+            "{}signature from rustreleaser secret key\n{}\n{}{}\n{}\n",
+            UNTRUSTED_COMMENT_PREFIX,
+            STANDARD.encode([PREHASHED_SIGNATURE_ALGORITHM, &self.key_id, &signature].concat()),
+            TRUSTED_COMMENT_PREFIX,
+            trusted_comment,
+            STANDARD.encode(global_signature.to_bytes())
+        ))
+    }
+}
+
+/// A minisign public key
+#[derive(Debug)]
+pub struct PublicKey {
+    key_id: [u8; KEY_ID_LEN],
+    verifying_key: VerifyingKey,
+}
+
+impl PublicKey {
+    /// Reads the key file content, or only its base64 line
+    pub fn decode(encoded: &str) -> Result<Self> {
+        let bytes = decode_base64_line(encoded).context("Cannot read the minisign public key")?;
+        if bytes.len() != PUBLIC_KEY_LEN || &bytes[0..2] != SIGNATURE_ALGORITHM {
+            bail!(anyhow::anyhow!("not a minisign public key"));
+        }
+
+        Ok(PublicKey {
+            key_id: bytes[2..10].try_into()?,
+            verifying_key: VerifyingKey::from_bytes(bytes[10..].try_into()?)
+                .context("Invalid minisign public key")?,
+        })
+    }
+
+    /// The base64 line consumers pass to `minisign -P`
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(
+            [
+                SIGNATURE_ALGORITHM,
+                &self.key_id,
+                self.verifying_key.as_bytes(),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Checks the `.minisig` content against the data, returning its trusted comment
+    pub fn verify(&self, data: impl Read, signature: &str) -> Result<String> {
+        let mut lines = signature
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty());
+        let (Some(_), Some(encoded), Some(trusted_comment), Some(global_signature)) =
+            (lines.next(), lines.next(), lines.next(), lines.next())
+        else {
+            bail!(anyhow::anyhow!("incomplete minisign signature"));
+        };
+        let Some(trusted_comment) = trusted_comment.strip_prefix(TRUSTED_COMMENT_PREFIX) else {
+            bail!(anyhow::anyhow!(
+                "the minisign signature has no trusted comment"
+            ));
+        };
+
+        let bytes = STANDARD
+            .decode(encoded)
+            .context("Cannot read the minisign signature")?;
+        if bytes.len() != SIGNATURE_LEN {
+            bail!(anyhow::anyhow!("not a minisign signature"));
+        }
+        if bytes[2..10] != self.key_id {
+            bail!(anyhow::anyhow!(
+                "the signature was made with another key than {}",
+                key_id_hex(&self.key_id)
+            ));
+        }
+
+        let signature = Signature::from_slice(&bytes[10..])?;
+        match &bytes[0..2] {
+            PREHASHED_SIGNATURE_ALGORITHM => self.verifying_key.verify(&prehash(data)?, &signature),
+            SIGNATURE_ALGORITHM => {
+                let mut content = vec![];
+                io::copy(&mut { data }, &mut content)?;
+                self.verifying_key.verify(&content, &signature)
+            }
+            _ => bail!(anyhow::anyhow!("unsupported minisign signature algorithm")),
+        }
+        .context("The signature does not match the file")?;
+
+        let global_signature = Signature::from_slice(
+            &STANDARD
+                .decode(global_signature)
+                .context("Cannot read the minisign global signature")?,
+        )?;
+        self.verifying_key
+            .verify(
+                &[&bytes[10..], trusted_comment.as_bytes()].concat(),
+                &global_signature,
+            )
+            .context("The trusted comment does not match the signature")?;
+
+        Ok(trusted_comment.to_owned())
+    }
+}
+
+/// Decodes the last line that is not a comment, so a whole key file can be given
+fn decode_base64_line(encoded: &str) -> Result<Vec<u8>> {
+    let line = encoded
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with(UNTRUSTED_COMMENT_PREFIX))
+        .unwrap_or_default();
+
+    Ok(STANDARD.decode(line)?)
+}
+
+fn prehash(mut data: impl Read) -> Result<Vec<u8>> {
+    let mut hasher = Blake2b512::new();
+    io::copy(&mut data, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn key_checksum(key_id: &[u8], keypair: &[u8]) -> Vec<u8> {
+    let mut hasher = Blake2b256::new();
+    hasher.update(SIGNATURE_ALGORITHM);
+    hasher.update(key_id);
+    hasher.update(keypair);
+    hasher.finalize().to_vec()
+}
+
+/// The key id as minisign prints it
+fn key_id_hex(key_id: &[u8; KEY_ID_LEN]) -> String {
+    format!("{:016X}", u64::from_le_bytes(*key_id))
+}
+
+/// The scrypt stream the secret key is xored with, using the parameters libsodium derives
+/// from the limits
+fn kdf_stream(
+    password: &str,
+    salt: &[u8],
+    opslimit: u64,
+    memlimit: u64,
+    len: usize,
+) -> Result<Vec<u8>> {
+    let opslimit = opslimit.max(MIN_OPSLIMIT);
+    let r = u64::from(SCRYPT_R);
+
+    let log_n = |max_n: u64| {
+        (1..63)
+            .find(|log_n| 1u64 << log_n > max_n / 2)
+            .unwrap_or(63)
+    };
+    let (log_n, p) = if opslimit < memlimit / 32 {
+        (log_n(opslimit / (r * 4)), 1)
+    } else {
+        let log_n = log_n(memlimit / (r * 128));
+        let max_rp = ((opslimit / 4) / (1u64 << log_n)).min(0x3fff_ffff);
+        (log_n, (max_rp / r) as u32)
+    };
+
+    let params = scrypt::Params::new(log_n as u8, SCRYPT_R, p, SCRYPT_CHECK_LEN)
+        .map_err(|e| anyhow::anyhow!("Invalid scrypt parameters in the secret key: {}", e))?;
+    let mut stream = vec![0; len];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut stream)
+        .map_err(|e| anyhow::anyhow!("Cannot derive the secret key: {}", e))?;
+
+    Ok(stream)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    const OPSLIMIT: u64 = 32768;
+    const MEMLIMIT: u64 = 1 << 20;
+
+    /// A secret key file as `minisign -G` writes it, with cheap scrypt limits
+    pub(in crate::signing) fn secret_key_file(seed: [u8; 32], password: Option<&str>) -> String {
+        let signing_key = SigningKey::from_bytes(&seed);
+        let key_id = [1, 2, 3, 4, 5, 6, 7, 8];
+        let keypair = signing_key.to_keypair_bytes();
+        let salt = [7; 32];
+
+        let mut keynum = [&key_id[..], &keypair, &key_checksum(&key_id, &keypair)].concat();
+        let kdf = match password {
+            Some(password) => {
+                let stream = kdf_stream(password, &salt, OPSLIMIT, MEMLIMIT, keynum.len()).unwrap();
+                keynum
+                    .iter_mut()
+                    .zip(stream)
+                    .for_each(|(byte, key)| *byte ^= key);
+                KDF_ALGORITHM
+            }
+            None => NO_KDF_ALGORITHM,
+        };
+
+        let bytes = [
+            SIGNATURE_ALGORITHM,
+            kdf,
+            CHECKSUM_ALGORITHM,
+            &salt,
+            &OPSLIMIT.to_le_bytes(),
+            &MEMLIMIT.to_le_bytes(),
+            &keynum,
+        ]
+        .concat();
+
+        format!(
+            "untrusted comment: minisign encrypted secret key\n{}\n",
+            STANDARD.encode(bytes)
+        )
+    }
+
+    #[test]
+    fn should_sign_like_minisign() -> Result<()> {
+        let secret_key =
+            SecretKey::decode(&secret_key_file([9; 32], Some("secret")), Some("secret"))?;
+        let public_key = secret_key.public_key();
+        let signature = secret_key.sign(&b"archive"[..], "timestamp:1\tfile:tool.tar.gz")?;
+
+        let verifier = minisign_verify::PublicKey::from_base64(&public_key.to_base64()).unwrap();
+        let decoded = minisign_verify::Signature::decode(&signature).unwrap();
+        assert!(verifier.verify(b"archive", &decoded, false).is_ok());
+        assert!(verifier.verify(b"tampered", &decoded, false).is_err());
+
+        assert_eq!(
+            public_key.verify(&b"archive"[..], &signature)?,
+            "timestamp:1\tfile:tool.tar.gz"
+        );
+        assert!(public_key.verify(&b"tampered"[..], &signature).is_err());
+        let forged = signature.replace("tool.tar.gz", "other.tar.gz");
+        assert!(public_key.verify(&b"archive"[..], &forged).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_need_the_right_password_for_encrypted_keys() {
+        let encrypted = secret_key_file([9; 32], Some("secret"));
+
+        assert!(SecretKey::decode(&encrypted, None)
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("password is missing"));
+        assert!(SecretKey::decode(&encrypted, Some("wrong")).is_err());
+        assert!(SecretKey::decode(&secret_key_file([9; 32], None), None).is_ok());
+    }
+}
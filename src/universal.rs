@@ -0,0 +1,183 @@
+use crate::{
+    build::{arch::Arch, os::Os, Build, TargetType},
+    cargo::arch_os_matrix::ArchOsMatrixEntry,
+    object_header::{
+        ObjectHeader, MACHO_CPU_ARM64, MACHO_FAT_MAGIC, MACHO_MAGIC_32, MACHO_MAGIC_64,
+    },
+};
+use anyhow::{bail, Context, Result};
+use std::{fs, path::Path};
+
+/// The pseudo target holding the universal darwin binaries
+pub const UNIVERSAL_TARGET: &str = "universal-apple-darwin";
+
+const FAT_HEADER_SIZE: usize = 8;
+const FAT_ARCH_SIZE: usize = 20;
+const ARM64_ALIGN: u32 = 14;
+const DEFAULT_ALIGN: u32 = 12;
+
+/// Merges the x86_64 and aarch64 darwin binaries into universal ones, when configured
+pub fn merge(build: &Build) -> Result<()> {
+    if !build.universal {
+        return Ok(());
+    }
+
+    if build.target_type() != TargetType::Multi {
+        bail!(anyhow::anyhow!(
+            "universal binaries need a multi target build"
+        ));
+    }
+
+    let matrix = Vec::<ArchOsMatrixEntry>::from(build.to_owned());
+    let darwin_target = |arch: Arch| {
+        matrix
+            .iter()
+            .find(|entry| entry.os == Os::AppleDarwin && entry.arch == arch)
+            .map(|entry| entry.to_string())
+    };
+    let (Some(x86_64), Some(aarch64)) = (darwin_target(Arch::Amd64), darwin_target(Arch::Arm64))
+    else {
+        bail!(anyhow::anyhow!(
+            "universal binaries need both the x86_64 and aarch64 apple-darwin targets"
+        ));
+    };
+
+    let dir = build.binaries_dir(Some(UNIVERSAL_TARGET));
+    fs::create_dir_all(&dir)?;
+
+    for binary in &build.binary {
+        log::info!("merging {} into a universal binary", binary.name);
+        let thin_binaries = [
+            fs::read(build.binaries_dir(Some(&x86_64)).join(&binary.name))?,
+            fs::read(build.binaries_dir(Some(&aarch64)).join(&binary.name))?,
+        ];
+
+        let path = dir.join(&binary.name);
+        write_fat_binary(&thin_binaries, &path)
+            .with_context(|| format!("Cannot merge {}", binary.name))?;
+        set_executable(&path)?;
+
+        let archs = ObjectHeader::read(&path)?.archs;
+        if !(archs.contains(&Arch::Amd64) && archs.contains(&Arch::Arm64)) {
+            bail!(anyhow::anyhow!(
+                "{} is missing an architecture: {:?}",
+                path.display(),
+                archs
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a Mach-O fat binary holding every thin binary, each aligned for its cpu type
+fn write_fat_binary(thin_binaries: &[Vec<u8>], path: &Path) -> Result<()> {
+    let mut header = MACHO_FAT_MAGIC.to_vec();
+    header.extend(u32::try_from(thin_binaries.len())?.to_be_bytes());
+
+    let mut offset = FAT_HEADER_SIZE + FAT_ARCH_SIZE * thin_binaries.len();
+    let mut slices = vec![];
+    for thin in thin_binaries {
+        let (cpu_type, cpu_subtype) = thin_cpu(thin)?;
+        let align = match cpu_type {
+            MACHO_CPU_ARM64 => ARM64_ALIGN,
+            _ => DEFAULT_ALIGN,
+        };
+        offset = offset.next_multiple_of(1 << align);
+
+        header.extend(cpu_type.to_be_bytes());
+        header.extend(cpu_subtype.to_be_bytes());
+        header.extend(u32::try_from(offset)?.to_be_bytes());
+        header.extend(u32::try_from(thin.len())?.to_be_bytes());
+        header.extend(align.to_be_bytes());
+
+        slices.push((offset, thin));
+        offset += thin.len();
+    }
+
+    let mut data = header;
+    for (offset, thin) in slices {
+        data.resize(offset, 0);
+        data.extend(thin);
+    }
+
+    fs::write(path, data)?;
+
+    Ok(())
+}
+
+/// The cpu type and subtype of a thin little endian Mach-O binary
+fn thin_cpu(thin: &[u8]) -> Result<(u32, u32)> {
+    if thin.len() < 12 || !(thin[0..4] == MACHO_MAGIC_32 || thin[0..4] == MACHO_MAGIC_64) {
+        bail!(anyhow::anyhow!("not a thin Mach-O binary"));
+    }
+
+    let cpu_type = u32::from_le_bytes(thin[4..8].try_into()?);
+    let cpu_subtype = u32::from_le_bytes(thin[8..12].try_into()?);
+
+    Ok((cpu_type, cpu_subtype))
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_header::MACHO_CPU_X86_64;
+    use tempdir::TempDir;
+
+    fn thin(cpu_type: u32, body: &[u8]) -> Vec<u8> {
+        let mut thin = MACHO_MAGIC_64.to_vec();
+        thin.extend(cpu_type.to_le_bytes());
+        thin.extend(3u32.to_le_bytes());
+        thin.extend(body);
+        thin
+    }
+
+    #[test]
+    fn should_write_aligned_slices() -> Result<()> {
+        let dir = TempDir::new("universal")?;
+        let path = dir.path().join("tool");
+        let x86_64 = thin(MACHO_CPU_X86_64, b"intel");
+        let aarch64 = thin(MACHO_CPU_ARM64, b"apple silicon");
+
+        write_fat_binary(&[x86_64.to_owned(), aarch64.to_owned()], &path)?;
+
+        let data = fs::read(&path)?;
+        let field = |arch: usize, field: usize| {
+            let start = FAT_HEADER_SIZE + arch * FAT_ARCH_SIZE + field * 4;
+            u32::from_be_bytes(data[start..start + 4].try_into().unwrap()) as usize
+        };
+
+        assert_eq!(field(0, 2), 1 << DEFAULT_ALIGN);
+        assert_eq!(field(1, 2), 1 << ARM64_ALIGN);
+        assert_eq!(field(1, 3), aarch64.len());
+        assert_eq!(&data[field(0, 2)..field(0, 2) + x86_64.len()], &x86_64[..]);
+        assert_eq!(&data[field(1, 2)..], &aarch64[..]);
+        assert_eq!(
+            ObjectHeader::read(&path)?.archs,
+            vec![Arch::Amd64, Arch::Arm64]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_non_macho_binaries() {
+        let dir = TempDir::new("universal").unwrap();
+        let elf = b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00".to_vec();
+
+        assert!(write_fat_binary(&[elf], &dir.path().join("tool")).is_err());
+    }
+}